@@ -0,0 +1,157 @@
+use crate::kzg10;
+use crate::transcript::TranscriptProtocol;
+use algebra::bls12_381::Fr;
+use algebra::Bls12_381;
+use ff_fft::DensePolynomial as Polynomial;
+use poly_commit::kzg10::{Commitment, Powers, VerifierKey};
+use std::fmt::Debug;
+
+/// A polynomial commitment scheme which the multiset equality argument can be run over
+/// The scheme fixes the types of commitments and openings. Committing and opening is done with a `CommitKey`,
+/// and checking openings with an `OpeningKey`, so that keys which borrow their parameters, like `Powers<'a, _>`, can be used
+/// Openings are batched: several polynomials are opened at the same point using one aggregation challenge
+pub trait PolynomialCommitment {
+    type Commitment: Clone + Debug + PartialEq;
+    /// Proof that a list of committed polynomials evaluate to a list of values at a point
+    type Opening: Clone + Debug + PartialEq;
+
+    /// Adds a commitment to the transcript
+    fn append_commitment(
+        transcript: &mut dyn TranscriptProtocol,
+        label: &'static [u8],
+        commitment: &Self::Commitment,
+    );
+
+    /// Computes the commitment to c_0 * p_0 + c_1 * p_1 + ... + c_n * p_n from the commitments to each p_i
+    /// The scheme must be homomorphic, so that the verifier can compute the commitment to the aggregated table
+    /// from the column commitments
+    fn linear_combination(
+        commitments: &[&Self::Commitment],
+        coefficients: &[Fr],
+    ) -> Self::Commitment;
+}
+
+/// Key used by the prover to commit to and open polynomials
+pub trait CommitKey<PC: PolynomialCommitment> {
+    /// Returns the largest degree of a polynomial that can be committed to
+    fn max_degree(&self) -> usize;
+
+    /// Commits to a polynomial
+    fn commit(&self, polynomial: &Polynomial<Fr>) -> PC::Commitment;
+
+    /// Opens every polynomial at `point`, aggregating them with powers of `aggregation_challenge`
+    fn open(
+        &self,
+        polynomials: &[&Polynomial<Fr>],
+        point: Fr,
+        aggregation_challenge: Fr,
+    ) -> PC::Opening;
+}
+
+/// Key used by the verifier to check openings
+pub trait OpeningKey<PC: PolynomialCommitment> {
+    /// Checks an opening created by `CommitKey::open` with the same aggregation challenge
+    fn verify_opening(&self, claim: &OpeningClaim<PC>, aggregation_challenge: Fr) -> bool;
+
+    /// Checks several openings which used the same aggregation challenge
+    /// By default each opening is checked on its own
+    fn verify_openings(&self, claims: &[OpeningClaim<PC>], aggregation_challenge: Fr) -> bool {
+        claims
+            .iter()
+            .all(|claim| self.verify_opening(claim, aggregation_challenge))
+    }
+}
+
+/// A claim that the committed polynomials evaluate to `values` at `point`, along with the opening proving it
+pub struct OpeningClaim<'a, PC: PolynomialCommitment> {
+    pub commitments: Vec<&'a PC::Commitment>,
+    pub point: Fr,
+    pub values: Vec<Fr>,
+    pub opening: &'a PC::Opening,
+}
+
+/// The KZG10 commitment scheme over BLS12-381, using the functions in `kzg10`
+/// The opening is the commitment to the aggregate witness polynomial
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KZG10Scheme;
+
+impl PolynomialCommitment for KZG10Scheme {
+    type Commitment = Commitment<Bls12_381>;
+    type Opening = Commitment<Bls12_381>;
+
+    fn append_commitment(
+        transcript: &mut dyn TranscriptProtocol,
+        label: &'static [u8],
+        commitment: &Self::Commitment,
+    ) {
+        transcript.append_commitment(label, commitment)
+    }
+
+    fn linear_combination(
+        commitments: &[&Self::Commitment],
+        coefficients: &[Fr],
+    ) -> Self::Commitment {
+        kzg10::linear_combination_commitments(commitments.to_vec(), coefficients)
+    }
+}
+
+impl<'a> CommitKey<KZG10Scheme> for Powers<'a, Bls12_381> {
+    fn max_degree(&self) -> usize {
+        kzg10::max_degree(self)
+    }
+
+    fn commit(&self, polynomial: &Polynomial<Fr>) -> Commitment<Bls12_381> {
+        kzg10::commit(self, polynomial)
+    }
+
+    fn open(
+        &self,
+        polynomials: &[&Polynomial<Fr>],
+        point: Fr,
+        aggregation_challenge: Fr,
+    ) -> Commitment<Bls12_381> {
+        let witness =
+            kzg10::compute_aggregate_witness(polynomials.to_vec(), point, aggregation_challenge);
+        kzg10::commit(self, &witness)
+    }
+}
+
+impl OpeningKey<KZG10Scheme> for VerifierKey<Bls12_381> {
+    fn verify_opening(&self, claim: &OpeningClaim<KZG10Scheme>, aggregation_challenge: Fr) -> bool {
+        let (commitment, value) = aggregate_claim(claim, aggregation_challenge);
+        kzg10::verify(self, &commitment, claim.opening, claim.point, value)
+    }
+
+    // The openings are checked together using one randomised batch check
+    fn verify_openings(
+        &self,
+        claims: &[OpeningClaim<KZG10Scheme>],
+        aggregation_challenge: Fr,
+    ) -> bool {
+        let mut commitments = Vec::with_capacity(claims.len());
+        let mut values = Vec::with_capacity(claims.len());
+        for claim in claims {
+            let (commitment, value) = aggregate_claim(claim, aggregation_challenge);
+            commitments.push(commitment);
+            values.push(value);
+        }
+
+        kzg10::batch_verify(
+            self,
+            commitments,
+            claims.iter().map(|claim| *claim.opening).collect(),
+            claims.iter().map(|claim| claim.point).collect(),
+            values,
+        )
+    }
+}
+
+// Aggregates the commitments and values of a claim with powers of the aggregation challenge
+fn aggregate_claim(
+    claim: &OpeningClaim<KZG10Scheme>,
+    aggregation_challenge: Fr,
+) -> (Commitment<Bls12_381>, Fr) {
+    let commitment = kzg10::aggregate_commitments(claim.commitments.clone(), aggregation_challenge);
+    let value = kzg10::aggregate_values(claim.values.iter().collect(), aggregation_challenge);
+    (commitment, value)
+}
@@ -1,11 +1,18 @@
+#[cfg(feature = "zeroize")]
+use algebra::bls12_381::G2Projective;
 use algebra::bls12_381::{Fr, G1Projective};
 use algebra::Bls12_381;
-use algebra_core::curves::AffineCurve;
-use ff_fft::DensePolynomial as Polynomial;
-use num_traits::identities::Zero;
+#[cfg(feature = "zeroize")]
+use algebra::UniformRand;
+use algebra_core::curves::{AffineCurve, ProjectiveCurve};
+use algebra_core::fields::Field;
+use ff_fft::{DensePolynomial as Polynomial, EvaluationDomain};
+use num_traits::identities::{One, Zero};
 use poly_commit::kzg10::{Commitment, Powers, Proof, UniversalParams, VerifierKey, KZG10};
 use rand_chacha::ChaChaRng;
 use rand_core::SeedableRng;
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, Zeroizing};
 // Modification of https://github.com/scipr-lab/poly-commit/blob/master/src/kzg10/mod.rs
 type KzgBls12_381 = KZG10<Bls12_381>;
 
@@ -18,6 +25,59 @@ pub fn trusted_setup<'a>(
     trim(&pp, max_deg)
 }
 
+/// Creates the same keys as `trusted_setup` for the same seed, however the secret `beta` and each of its powers
+/// are zeroized once the powers of g have been computed, so that they are not left in memory
+/// The keys only contain group elements, so the secret is not retained in the returned structures
+#[cfg(feature = "zeroize")]
+pub fn trusted_setup_zeroized<'a>(
+    max_deg: usize,
+    seed: &[u8],
+) -> (Powers<'a, Bls12_381>, VerifierKey<Bls12_381>) {
+    let mut rng = ChaChaRng::from_seed(to_32_bytes(seed));
+    let pp = setup_zeroized(max_deg, &mut rng);
+    trim(&pp, max_deg)
+}
+
+// Computes the same parameters as `KZG10::setup`, sampling the secret and the generators from `rng`
+// in the same order, so that the parameters are the same for the same seed
+#[cfg(feature = "zeroize")]
+fn setup_zeroized(max_deg: usize, rng: &mut ChaChaRng) -> UniversalParams<Bls12_381> {
+    let beta = Zeroizing::new(SecretScalar(Fr::rand(rng)));
+    let g = G1Projective::rand(rng).into_affine();
+    let gamma_g = G1Projective::rand(rng).into_affine();
+    let h = G2Projective::rand(rng).into_affine();
+
+    let mut powers_of_g = Vec::with_capacity(max_deg + 1);
+    let mut powers_of_gamma_g = Vec::with_capacity(max_deg + 1);
+    let mut power_of_beta = Zeroizing::new(SecretScalar(Fr::one()));
+    for _ in 0..=max_deg {
+        powers_of_g.push(g.mul(power_of_beta.0).into_affine());
+        powers_of_gamma_g.push(gamma_g.mul(power_of_beta.0).into_affine());
+        power_of_beta.0 *= &beta.0;
+    }
+    let beta_h = h.mul(beta.0).into_affine();
+
+    UniversalParams {
+        powers_of_g,
+        powers_of_gamma_g,
+        h,
+        beta_h,
+        prepared_h: h.into(),
+        prepared_beta_h: beta_h.into(),
+    }
+}
+
+// A secret scalar, which is cleared when it is wrapped in `Zeroizing` and dropped
+#[cfg(feature = "zeroize")]
+struct SecretScalar(Fr);
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for SecretScalar {
+    fn zeroize(&mut self) {
+        (self.0).0 .0.zeroize()
+    }
+}
+
 fn to_32_bytes(bytes: &[u8]) -> [u8; 32] {
     let mut array: [u8; 32] = [0; 32];
     for (a, b) in bytes.iter().zip(array.iter_mut()) {
@@ -51,12 +111,83 @@ fn trim<'a>(
     (powers, vk)
 }
 
+/// Returns the largest degree of a polynomial that can be committed to using `powers`
+pub fn max_degree(powers: &Powers<Bls12_381>) -> usize {
+    powers.powers_of_g.len() - 1
+}
+
+/// Returns the smallest maximum degree of a setup which can prove lookups into a table with `table_len` rows
+/// The table is preprocessed to a size `n`, the next power of two, and the quotient polynomial has degree 2n - 2,
+/// which is the largest polynomial committed to by the prover
+/// Proving with a smaller setup returns `LookUpError::SetupTooSmall`
+pub fn required_setup_degree(table_len: usize) -> usize {
+    let n = table_len.max(2).next_power_of_two();
+    2 * n - 2
+}
+
 pub fn commit(powers: &Powers<Bls12_381>, p: &Polynomial<Fr>) -> Commitment<Bls12_381> {
     let hiding_bound = None;
     let (comm, _) = KZG10::commit(&powers, &p, hiding_bound, None).unwrap();
     comm
 }
 
+/// Returns the commitment to each Lagrange basis polynomial L_i of `domain`, the polynomial which is one at the
+/// i-th element of the domain and zero at every other element
+/// A polynomial given by its evaluations over the domain can then be committed to without interpolating it,
+/// as the sum of each evaluation times the commitment to the basis polynomial at its position
+/// Panics if `powers` can not commit to polynomials of degree `domain.size() - 1`
+pub fn lagrange_basis_commitments(
+    powers: &Powers<Bls12_381>,
+    domain: &EvaluationDomain<Fr>,
+) -> Vec<Commitment<Bls12_381>> {
+    let n = domain.size();
+    assert!(max_degree(powers) + 1 >= n);
+
+    // The commitment to L_i is 1/n * sum_j omega^{-ij} * g^{beta^j},
+    // so the commitments are the inverse FFT of the powers of g
+    let mut points: Vec<G1Projective> = powers.powers_of_g[..n]
+        .iter()
+        .map(|g| g.into_projective())
+        .collect();
+    group_fft(&mut points, domain.group_gen_inv);
+    points
+        .into_iter()
+        .map(|point| Commitment(point.mul(domain.size_inv).into()))
+        .collect()
+}
+
+// Computes the FFT of `points` in place, using the `points.len()`-th root of unity `omega`
+// This is the radix-2 FFT over the field, with group elements in place of field elements
+fn group_fft(points: &mut [G1Projective], omega: Fr) {
+    let n = points.len();
+    if n <= 1 {
+        return;
+    }
+    let log_n = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (8 * std::mem::size_of::<usize>() as u32 - log_n);
+        if i < j {
+            points.swap(i, j);
+        }
+    }
+
+    let mut half = 1;
+    while half < n {
+        // A primitive (2 * half)-th root of unity
+        let step = omega.pow(&[(n / (2 * half)) as u64]);
+        for start in (0..n).step_by(2 * half) {
+            let mut w = Fr::one();
+            for i in start..start + half {
+                let t = points[i + half].mul(w);
+                points[i + half] = points[i] - &t;
+                points[i] += &t;
+                w *= &step;
+            }
+        }
+        half *= 2;
+    }
+}
+
 pub fn commit_vec(powers: &Powers<Bls12_381>, p_vec: &Vec<Fr>) -> Commitment<Bls12_381> {
     let p = Polynomial::from_coefficients_slice(p_vec);
     commit(powers, &p)
@@ -106,6 +237,31 @@ pub fn aggregate_commitments(
 
     Commitment(result.into())
 }
+// Computes the commitment to p_0 + p_1 + ... + p_n from the commitments to each p_i
+// With no commitments, this is the commitment to the zero polynomial
+pub fn sum_commitments(commitments: Vec<&Commitment<Bls12_381>>) -> Commitment<Bls12_381> {
+    let mut result = G1Projective::zero();
+
+    for commitment in commitments {
+        result += &commitment.0.into_projective();
+    }
+
+    Commitment(result.into())
+}
+// Computes the commitment to c_0 * p_0 + c_1 * p_1 + ... + c_n * p_n from the commitments to each p_i
+pub fn linear_combination_commitments(
+    commitments: Vec<&Commitment<Bls12_381>>,
+    coefficients: &[Fr],
+) -> Commitment<Bls12_381> {
+    assert_eq!(commitments.len(), coefficients.len());
+    let mut result = G1Projective::zero();
+
+    for (commitment, coefficient) in commitments.into_iter().zip(coefficients.iter()) {
+        result += &commitment.0.mul(*coefficient);
+    }
+
+    Commitment(result.into())
+}
 pub fn aggregate_values(values: Vec<&Fr>, aggregation_challenge: Fr) -> Fr {
     let mut powers = Fr::from(1u8);
     let mut result = Fr::zero();
@@ -159,3 +315,158 @@ pub fn batch_verify(
     )
     .unwrap()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_max_degree() {
+        let (proving_key, _) = trusted_setup(2usize.pow(12), b"insecure_seed");
+        assert_eq!(max_degree(&proving_key), 2usize.pow(12));
+
+        // A polynomial with the maximum degree can be committed to
+        let p =
+            Polynomial::from_coefficients_vec(vec![Fr::from(1u8); max_degree(&proving_key) + 1]);
+        commit(&proving_key, &p);
+    }
+    #[test]
+    fn test_lagrange_basis_commitments() {
+        let (proving_key, _) = trusted_setup(2usize.pow(4), b"insecure_seed");
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(8).unwrap();
+
+        // Each commitment is the commitment to the interpolated basis polynomial
+        let commitments = lagrange_basis_commitments(&proving_key, &domain);
+        assert_eq!(commitments.len(), 8);
+        for (i, commitment) in commitments.iter().enumerate() {
+            let mut evaluations = vec![Fr::zero(); 8];
+            evaluations[i] = Fr::one();
+            let poly = Polynomial::from_coefficients_vec(domain.ifft(&evaluations));
+            assert_eq!(*commitment, commit(&proving_key, &poly));
+        }
+
+        // So a polynomial can be committed to from its evaluations
+        let evaluations: Vec<Fr> = (0..8u64).map(|i| Fr::from(i * i + 1)).collect();
+        let poly = Polynomial::from_coefficients_vec(domain.ifft(&evaluations));
+        assert_eq!(
+            linear_combination_commitments(commitments.iter().collect(), &evaluations),
+            commit(&proving_key, &poly)
+        );
+    }
+    #[test]
+    fn test_required_setup_degree() {
+        use crate::lookup::{LookUp, LookUpError};
+        use crate::lookup_table::{LookUpTable, XOR4BitTable};
+        use merlin::Transcript;
+
+        assert_eq!(required_setup_degree(0), 2);
+        assert_eq!(required_setup_degree(3), 6);
+        assert_eq!(required_setup_degree(4), 6);
+        assert_eq!(required_setup_degree(5), 14);
+
+        // A setup of the suggested degree is enough to prove lookups into the 4 bit XOR table
+        let table = XOR4BitTable::new();
+        let degree = required_setup_degree(table.len());
+        assert_eq!(degree, 2 * 256 - 2);
+        let (proving_key, verifier_key) = trusted_setup(degree, b"insecure_seed");
+
+        let preprocessed_table = table.preprocess(&proving_key, table.len().next_power_of_two());
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(15u8), Fr::from(15u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        // A setup of one degree less can still pre-process the table, whose columns have degree n - 1,
+        // but it can not commit to the quotient polynomial, so proving returns an error
+        let table = XOR4BitTable::new();
+        let degree = required_setup_degree(table.len()) - 1;
+        let (proving_key, _) = trusted_setup(degree, b"insecure_seed");
+
+        let preprocessed_table = table.preprocess(&proving_key, table.len().next_power_of_two());
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(15u8), Fr::from(15u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let result = lookup.prove(&proving_key, &preprocessed_table, &mut prover_transcript);
+        assert_eq!(
+            result.err(),
+            Some(LookUpError::SetupTooSmall {
+                degree: 2 * 256 - 3,
+                required: 2 * 256 - 2,
+            })
+        );
+    }
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize_setup() {
+        use algebra_core::serialize::CanonicalSerialize;
+
+        let (proving_key, verifier_key) = trusted_setup_zeroized(2usize.pow(4), b"insecure_seed");
+
+        // The keys are the same as the keys created without zeroizing
+        let (expected_proving_key, expected_verifier_key) =
+            trusted_setup(2usize.pow(4), b"insecure_seed");
+        assert_eq!(proving_key.powers_of_g, expected_proving_key.powers_of_g);
+        assert_eq!(
+            proving_key.powers_of_gamma_g,
+            expected_proving_key.powers_of_gamma_g
+        );
+        assert_eq!(verifier_key.g, expected_verifier_key.g);
+        assert_eq!(verifier_key.gamma_g, expected_verifier_key.gamma_g);
+        assert_eq!(verifier_key.h, expected_verifier_key.h);
+        assert_eq!(verifier_key.beta_h, expected_verifier_key.beta_h);
+
+        // The secret is the first value sampled from the seed
+        let mut rng = ChaChaRng::from_seed(to_32_bytes(b"insecure_seed"));
+        let beta = Fr::rand(&mut rng);
+        let g = proving_key.powers_of_g[0];
+        assert_eq!(proving_key.powers_of_g[1], g.mul(beta).into_affine());
+
+        // It does not appear anywhere in the keys
+        let mut secret = Vec::new();
+        beta.serialize(&mut secret).unwrap();
+        let mut keys = Vec::new();
+        let generators = [verifier_key.g, verifier_key.gamma_g];
+        let points = proving_key
+            .powers_of_g
+            .iter()
+            .chain(proving_key.powers_of_gamma_g.iter())
+            .chain(generators.iter());
+        for point in points {
+            point.serialize(&mut keys).unwrap();
+        }
+        verifier_key.h.serialize(&mut keys).unwrap();
+        verifier_key.beta_h.serialize(&mut keys).unwrap();
+        assert!(!keys.windows(secret.len()).any(|bytes| bytes == &secret[..]));
+
+        // The parameters created with a zeroized secret can still be used to commit and open
+        let p =
+            Polynomial::from_coefficients_vec(vec![Fr::from(3u8), Fr::from(5u8), Fr::from(7u8)]);
+        let point = Fr::from(11u8);
+        let commitment = commit(&proving_key, &p);
+        let witness = commit(&proving_key, &compute_witness(&p, point));
+        assert!(verify(
+            &verifier_key,
+            &commitment,
+            &witness,
+            point,
+            p.evaluate(point)
+        ));
+
+        // `Zeroizing` clears the secret like this when it is dropped
+        let mut secret = Zeroizing::new(SecretScalar(beta));
+        secret.zeroize();
+        assert!(secret.0.is_zero());
+    }
+}
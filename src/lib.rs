@@ -1,8 +1,11 @@
+pub mod commitment;
 pub mod kzg10;
 pub mod lookup;
 pub mod lookup_table;
 pub mod multiset;
 pub mod multiset_equality;
+pub mod parallel;
+pub mod prelude;
 pub mod proof;
 pub mod quotient_poly;
 pub mod transcript;
@@ -1,14 +1,50 @@
+use crate::commitment::{CommitKey, KZG10Scheme, PolynomialCommitment};
 use crate::kzg10;
-use crate::lookup_table::{LookUpTable, PreProcessedTable};
+use crate::lookup_table::{
+    AggregationStrategy, LookUpTable, MergedTableCache, PreProcessedTable,
+    PreProcessedTwoOutputTable, ReadResult, SortOrder, TableColumns, TwoOutputLookUpTable,
+};
 use crate::multiset::MultiSet;
-use crate::multiset_equality;
+use crate::multiset_equality::{self, Accumulator};
 use crate::proof::{Commitments, Evaluations, MultiSetEqualityProof};
 use crate::quotient_poly;
-use crate::transcript::TranscriptProtocol;
+use crate::transcript::{labels, TranscriptProtocol};
+#[cfg(feature = "record")]
+use crate::transcript::{RecordingTranscript, TranscriptOp};
 use algebra::bls12_381::Fr;
 use algebra::Bls12_381;
 use ff_fft::{DensePolynomial as Polynomial, EvaluationDomain};
-use poly_commit::kzg10::Powers;
+use num_traits::identities::{One, Zero};
+use poly_commit::kzg10::{Commitment, Powers};
+use std::collections::{HashMap, HashSet};
+
+/// Errors that can occur when creating a lookup proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookUpError {
+    /// The key being read is not in the table
+    KeyNotFound,
+    /// The aggregated witness must have exactly one element less than the aggregated table
+    InvalidWitnessSize { witness: usize, table: usize },
+    /// A zero challenge or coefficient would collapse a column when aggregating
+    ZeroChallenge,
+    /// The left, right and output wires must have the same number of elements
+    WireLengthMismatch,
+    /// The rows at these indices are not in the table
+    RowsNotInTable(Vec<usize>),
+    /// No values were read from the table, so there is no witness to prove
+    NoReads,
+    /// There were `reads` reads, but a table of this size can only prove `capacity` reads
+    WitnessExceedsTable { reads: usize, capacity: usize },
+    /// The witness must have one wire for each column of the table
+    ColumnCountMismatch { witness: usize, table: usize },
+    /// The setup can commit to polynomials of degree `degree`, but proving against the table needs `required`
+    SetupTooSmall { degree: usize, required: usize },
+    /// The coset shift `k` is zero, or `k^n` is a 4th root of unity, so the quotient polynomial can not be computed
+    InvalidCosetShift,
+    /// The table is not the one that the streaming lookup was created for
+    TableMismatch,
+}
+
 pub struct LookUp<T: LookUpTable> {
     table: T,
     // This is the set of values which we want to prove is a subset of the
@@ -16,6 +52,12 @@ pub struct LookUp<T: LookUpTable> {
     left_wires: MultiSet,
     right_wires: MultiSet,
     output_wires: MultiSet,
+    // How the columns are combined into one multiset
+    strategy: AggregationStrategy,
+    // How the table is ordered, and how the witness is sorted by it
+    sort_order: SortOrder,
+    // The coset of the 4n domain that the quotient polynomial is computed on
+    coset_shift: Fr,
 }
 
 impl<T: LookUpTable> LookUp<T> {
@@ -25,81 +67,915 @@ impl<T: LookUpTable> LookUp<T> {
             left_wires: MultiSet::new(),
             right_wires: MultiSet::new(),
             output_wires: MultiSet::new(),
+            strategy: AggregationStrategy::default(),
+            sort_order: SortOrder::default(),
+            coset_shift: quotient_poly::default_coset_shift(),
+        }
+    }
+    /// Sets the strategy used to combine the columns of the table and witness
+    pub fn with_strategy(mut self, strategy: AggregationStrategy) -> LookUp<T> {
+        self.strategy = strategy;
+        self
+    }
+    /// Sets the order of the table, which the witness is sorted by
+    /// The verifier must use the same order, see `MultiSetEqualityProof::verify_with_sort_order`
+    pub fn with_sort_order(mut self, sort_order: SortOrder) -> LookUp<T> {
+        self.sort_order = sort_order;
+        self
+    }
+    /// Sets the coset shift `k`, so that the quotient polynomial is computed on the coset `k * H_4n`
+    /// `k^n` must not be a 4th root of unity, otherwise the coset intersects the domain and proving
+    /// returns `LookUpError::InvalidCosetShift`
+    /// This only affects the prover, the proof is the same for any valid shift
+    pub fn with_coset_shift(mut self, coset_shift: Fr) -> LookUp<T> {
+        self.coset_shift = coset_shift;
+        self
+    }
+    /// Creates a lookup from wire values which have already been assigned, for example by a circuit
+    /// Each row (left[i], right[i], output[i]) is checked against the table
+    /// If any rows are not in the table, their indices are returned
+    pub fn from_wires(
+        table: T,
+        left_wires: MultiSet,
+        right_wires: MultiSet,
+        output_wires: MultiSet,
+    ) -> Result<LookUp<T>, LookUpError> {
+        if left_wires.len() != right_wires.len() || left_wires.len() != output_wires.len() {
+            return Err(LookUpError::WireLengthMismatch);
+        }
+
+        let lookup = LookUp {
+            table: table,
+            left_wires: left_wires,
+            right_wires: right_wires,
+            output_wires: output_wires,
+            strategy: AggregationStrategy::default(),
+            sort_order: SortOrder::default(),
+            coset_shift: quotient_poly::default_coset_shift(),
+        };
+        lookup
+            .validate_rows()
+            .map_err(LookUpError::RowsNotInTable)?;
+
+        Ok(lookup)
+    }
+    /// Checks that every row (left[i], right[i], output[i]) of the witness is a row in the table
+    /// Returns the indices of the rows which are not
+    pub fn validate_rows(&self) -> Result<(), Vec<usize>> {
+        let invalid_rows: Vec<usize> = (0..self.left_wires.len())
+            .filter(|&i| {
+                let key = (self.left_wires.0[i], self.right_wires.0[i]);
+                self.table.read(&key) != Some(&self.output_wires.0[i])
+            })
+            .collect();
+        if invalid_rows.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid_rows)
         }
     }
     // First reads a value from the underlying table
     // Then we add the key and value to their respective multisets
     // Returns true if the value existed in the table
     pub fn read(&mut self, key: &(Fr, Fr)) -> bool {
-        let option_output = self.table.read(key);
-        if option_output.is_none() {
-            return false;
+        matches!(self.read_checked(key), ReadResult::Found(_))
+    }
+    /// Reads a value from the underlying table like `read`,
+    /// but reports whether a missing key was outside of the table's input domain
+    pub fn read_checked(&mut self, key: &(Fr, Fr)) -> ReadResult {
+        let result = self.table.read_checked(key);
+        if let ReadResult::Found(output) = result {
+            // Add (input, output) combination into the corresponding multisets
+            self.left_wires.push(key.0);
+            self.right_wires.push(key.1);
+            self.output_wires.push(output);
         }
-        let output = *option_output.unwrap();
-
-        // Add (input, output) combination into the corresponding multisets
-        self.left_wires.push(key.0);
-        self.right_wires.push(key.1);
-        self.output_wires.push(output);
-
-        return true;
+        result
     }
 
     /// Aggregates the table and witness values into one multiset
     /// sorts, and pads the witness and or table to be the correct size
+    /// Returns an error if `alpha` is zero, as the aggregation would be degenerate,
+    /// if there were no reads or if there were more reads than the table can prove
     pub fn to_multiset(
         &mut self,
         preprocessed_table: &PreProcessedTable,
         alpha: Fr,
-    ) -> (MultiSet, MultiSet) {
-        // Now we need to aggregate our table values into one multiset
-        let mut merged_table = MultiSet::aggregate(
-            vec![
-                &preprocessed_table.t_1.0,
-                &preprocessed_table.t_2.0,
-                &preprocessed_table.t_3.0,
-            ],
-            alpha,
-        );
-        // Sort merged table values
-        merged_table = merged_table.sort();
+    ) -> Result<(MultiSet, MultiSet), LookUpError> {
+        self.to_multiset_with_alpha(preprocessed_table, alpha, false)
+    }
 
-        // Pad left, right and output wires to be one less than the table multiset
-        let pad_by = preprocessed_table.n - 1 - self.left_wires.len();
-        self.left_wires.extend(pad_by, self.left_wires.last());
+    /// Aggregates the witness values and the distinct rows of the table into one multiset
+    /// See `PreProcessedTable::merged_table_distinct` for how this affects the table
+    /// This is only a helper for the multisets, proofs always use every row of the table
+    pub fn to_multiset_distinct_rows(
+        &mut self,
+        preprocessed_table: &PreProcessedTable,
+        alpha: Fr,
+    ) -> Result<(MultiSet, MultiSet), LookUpError> {
+        self.to_multiset_with_alpha(preprocessed_table, alpha, true)
+    }
+
+    // Aggregates the table and witness values into one multiset using powers of `alpha`
+    // If `distinct_rows` is set, duplicate rows in the table are removed before aggregating
+    fn to_multiset_with_alpha(
+        &mut self,
+        preprocessed_table: &PreProcessedTable,
+        alpha: Fr,
+        distinct_rows: bool,
+    ) -> Result<(MultiSet, MultiSet), LookUpError> {
+        if alpha.is_zero() {
+            return Err(LookUpError::ZeroChallenge);
+        }
+        let coefficients = [Fr::one(), alpha, alpha * alpha];
 
-        self.right_wires.extend(pad_by, self.right_wires.last());
+        // Pad left, right and output wires to be one less than the table multiset
+        pad_wires(
+            &mut [
+                &mut self.left_wires,
+                &mut self.right_wires,
+                &mut self.output_wires,
+            ],
+            preprocessed_table.n,
+        )?;
 
-        self.output_wires.extend(pad_by, self.output_wires.last());
+        // Now we need to aggregate our table values into one sorted multiset
+        let merged_table = if distinct_rows {
+            preprocessed_table.merged_table_distinct(&coefficients, self.sort_order)
+        } else {
+            preprocessed_table.merged_table_with_order(&coefficients, self.sort_order)
+        };
 
         // Now we need to aggregate our witness values into one multiset
-        let merged_witness = MultiSet::aggregate(
-            vec![&self.left_wires, &self.right_wires, &self.output_wires],
+        let merged_witness = MultiSet::aggregate3(
+            &self.left_wires,
+            &self.right_wires,
+            &self.output_wires,
             alpha,
         );
 
-        assert!(merged_witness.len() < merged_table.len()); // XXX: We could incorporate this in the API by counting the number of reads
-
-        (merged_witness, merged_table)
+        Ok((merged_witness, merged_table))
     }
 
     /// Creates a proof that the multiset is within the table
+    /// Returns `LookUpError::RowsNotInTable` with the indices of any reads which are not rows of the table
+    /// Returns `LookUpError::SetupTooSmall` before doing any work if the proving key can not commit to
+    /// the quotient polynomial, see `kzg10::required_setup_degree`
     pub fn prove(
         &mut self,
         proving_key: &Powers<Bls12_381>,
         preprocessed_table: &PreProcessedTable,
         transcript: &mut dyn TranscriptProtocol,
-    ) -> MultiSetEqualityProof {
-        // Generate alpha challenge
-        let alpha = transcript.challenge_scalar(b"alpha");
-        transcript.append_scalar(b"alpha", &alpha);
+    ) -> Result<MultiSetEqualityProof, LookUpError> {
+        let (proof, _) =
+            self.prove_with_accumulator(proving_key, preprocessed_table, transcript)?;
+        Ok(proof)
+    }
 
-        // Aggregate witness and table values using a random challenge
-        let (f, t) = self.to_multiset(preprocessed_table, alpha);
-        assert_eq!(f.len() + 1, t.len());
+    /// Creates a proof that the multiset is within the table
+    /// and returns the grand product accumulator Z(X) that was committed to in the proof
+    pub fn prove_with_accumulator(
+        &mut self,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(MultiSetEqualityProof, Accumulator), LookUpError> {
+        self.prove_inner::<KZG10Scheme, _>(proving_key, preprocessed_table, None, transcript)
+    }
+
+    /// Creates a proof like `prove`, taking the aggregated table from `cache` if it was aggregated with the same
+    /// coefficients, for example when proving again with a transcript in the same state
+    pub fn prove_cached(
+        &mut self,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+        cache: &MergedTableCache,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<MultiSetEqualityProof, LookUpError> {
+        let (proof, _) =
+            self.prove_inner(proving_key, preprocessed_table, Some(cache), transcript)?;
+        Ok(proof)
+    }
+
+    /// Creates a proof that the multiset is within a table pre-processed with the commitment scheme `PC`
+    /// With `SortOrder::TablePosition` the commitment to the aggregated table is computed from the column commitments
+    pub fn prove_with_scheme<PC: PolynomialCommitment, K: CommitKey<PC>>(
+        &mut self,
+        commit_key: &K,
+        preprocessed_table: &PreProcessedTable<PC>,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<MultiSetEqualityProof<PC>, LookUpError> {
+        let (proof, _) = self.prove_inner(commit_key, preprocessed_table, None, transcript)?;
+        Ok(proof)
+    }
+
+    fn prove_inner<PC: PolynomialCommitment, K: CommitKey<PC>>(
+        &mut self,
+        commit_key: &K,
+        preprocessed_table: &PreProcessedTable<PC>,
+        cache: Option<&MergedTableCache>,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(MultiSetEqualityProof<PC>, Accumulator<PC>), LookUpError> {
+        prove_wires(
+            &mut [
+                &mut self.left_wires,
+                &mut self.right_wires,
+                &mut self.output_wires,
+            ],
+            preprocessed_table,
+            cache,
+            self.strategy,
+            self.sort_order,
+            self.coset_shift,
+            commit_key,
+            transcript,
+        )
+    }
+
+    /// Creates a proof that the multiset is within the table
+    /// and returns every operation that was performed on the transcript while doing so
+    #[cfg(feature = "record")]
+    pub fn prove_recorded(
+        &mut self,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(MultiSetEqualityProof, Vec<TranscriptOp>), LookUpError> {
+        let mut recorder = RecordingTranscript::new(transcript);
+        let proof = self.prove(proving_key, preprocessed_table, &mut recorder)?;
+        Ok((proof, recorder.into_ops()))
+    }
+}
+
+/// Absorbs reads for a lookup in chunks and creates the proof once every chunk has been absorbed
+///
+/// Each wire polynomial, and so its commitment, is linear in the evaluations of the wire. The commitment to each
+/// Lagrange basis polynomial of the witness domain is computed once in `new`, and the commitment to each wire is kept
+/// as a running sum: every value read adds itself times the basis commitment at its position.
+/// In `finalize` the three wire commitments and the commitment to the padding are combined using the aggregation
+/// coefficients, like `PreparedLookUp`, so that the witness is not committed to again.
+/// The grand product Z(X) and the sorted h_1 and h_2 depend on every read, so the row of the table that each read
+/// is found at is kept, rather than the values that were read
+pub struct StreamingLookUp<T: LookUpTable> {
+    table: T,
+    n: usize,
+    strategy: AggregationStrategy,
+    sort_order: SortOrder,
+    coset_shift: Fr,
+    // The commitments to the columns of the table that the reads are found in
+    table_commitments: [Commitment<Bls12_381>; 3],
+    // The position of each row in the pre-processed table, the first one for repeated rows
+    row_positions: HashMap<(Fr, Fr, Fr), usize>,
+    // The commitment to each Lagrange basis polynomial of the witness domain
+    lagrange_commitments: Vec<Commitment<Bls12_381>>,
+    // The position in the table of each read, in the order they were read
+    rows: Vec<usize>,
+    // The sum of each value of the left, right and output wires times the basis commitment at its position
+    wire_commitments: [Commitment<Bls12_381>; 3],
+}
+
+impl<T: LookUpTable> StreamingLookUp<T> {
+    /// Creates a lookup for reads which are proven against `preprocessed_table`
+    /// This commits to every Lagrange basis polynomial of the witness domain, so that chunks can be committed to
+    /// without the proving key. Returns an error if `proving_key` can not prove lookups into the table
+    pub fn new(
+        table: T,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+    ) -> Result<StreamingLookUp<T>, LookUpError> {
+        let n = preprocessed_table.n;
+        let coset_shift = quotient_poly::default_coset_shift();
+        check_prover_setup(proving_key, n, coset_shift)?;
+
+        let (t_1, t_2, t_3) = (
+            &preprocessed_table.t_1,
+            &preprocessed_table.t_2,
+            &preprocessed_table.t_3,
+        );
+        let mut row_positions = HashMap::new();
+        for i in 0..n {
+            let row = (t_1.0 .0[i], t_2.0 .0[i], t_3.0 .0[i]);
+            row_positions.entry(row).or_insert(i);
+        }
+
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(n - 1).unwrap();
+        let zero = kzg10::sum_commitments(Vec::new());
+        Ok(StreamingLookUp {
+            table,
+            n,
+            strategy: AggregationStrategy::default(),
+            sort_order: SortOrder::default(),
+            coset_shift,
+            table_commitments: [t_1.1, t_2.1, t_3.1],
+            row_positions,
+            lagrange_commitments: kzg10::lagrange_basis_commitments(proving_key, &domain),
+            rows: Vec::new(),
+            wire_commitments: [zero, zero, zero],
+        })
+    }
+    /// Reads each key in the chunk from the table and adds the values that were read to the wire commitments
+    /// Keys which are not in the table are skipped, like `LookUp::read`
+    /// Returns the number of keys that were added to the witness, or an error if the table
+    /// can not prove that many reads, in which case nothing is added
+    /// Keys whose row is not in the pre-processed table are returned as `LookUpError::RowsNotInTable`,
+    /// with their indices in the chunk
+    pub fn absorb_chunk(&mut self, keys: &[(Fr, Fr)]) -> Result<usize, LookUpError> {
+        let mut values = [Vec::new(), Vec::new(), Vec::new()];
+        let mut rows = Vec::new();
+        let mut missing = Vec::new();
+        for (i, key) in keys.iter().enumerate() {
+            let output = match self.table.read(key) {
+                Some(output) => *output,
+                None => continue,
+            };
+            match self.row_positions.get(&(key.0, key.1, output)) {
+                Some(&position) => rows.push(position),
+                None => missing.push(i),
+            }
+            values[0].push(key.0);
+            values[1].push(key.1);
+            values[2].push(output);
+        }
+        if !missing.is_empty() {
+            return Err(LookUpError::RowsNotInTable(missing));
+        }
+
+        let offset = self.num_reads();
+        let reads = offset + rows.len();
+        let capacity = self.n.saturating_sub(1);
+        if reads > capacity {
+            return Err(LookUpError::WitnessExceedsTable { reads, capacity });
+        }
+
+        // Add the chunk to each wire commitment, which is a multi-scalar multiplication of the size of the chunk
+        let basis = &self.lagrange_commitments[offset..reads];
+        for (commitment, values) in self.wire_commitments.iter_mut().zip(values.iter()) {
+            let mut commitments = vec![&*commitment];
+            commitments.extend(basis.iter());
+            let mut coefficients = vec![Fr::one()];
+            coefficients.extend_from_slice(values);
+            *commitment = kzg10::linear_combination_commitments(commitments, &coefficients);
+        }
+        self.rows.extend(rows);
+
+        Ok(reads - offset)
+    }
+    /// Returns the number of reads absorbed so far
+    pub fn num_reads(&self) -> usize {
+        self.rows.len()
+    }
+    /// Creates a proof that all absorbed reads are within the table
+    /// `preprocessed_table` must be the table that the lookup was created for
+    /// This is equal to the proof that `LookUp::prove` would create for the same reads
+    pub fn finalize(
+        self,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<MultiSetEqualityProof, LookUpError> {
+        let n = self.n;
+        if preprocessed_table.n != n {
+            return Err(LookUpError::InvalidWitnessSize {
+                witness: n - 1,
+                table: preprocessed_table.n,
+            });
+        }
+        let table_commitments = [
+            preprocessed_table.t_1.1,
+            preprocessed_table.t_2.1,
+            preprocessed_table.t_3.1,
+        ];
+        if table_commitments != self.table_commitments {
+            return Err(LookUpError::TableMismatch);
+        }
+        check_prover_setup(proving_key, n, self.coset_shift)?;
+        let reads = self.num_reads();
+        let pad_by = padding(reads, n)?;
+
+        // Bind the size of the domain to the proof
+        transcript.append_scalar(labels::DOMAIN_SIZE, &Fr::from(n as u64));
+
+        // Generate the aggregation challenges
+        let coefficients = self.strategy.coefficients(transcript, 3);
+        self.sort_order.append_to_transcript(transcript);
+
+        // The aggregated witness is the aggregated row of each read, padded by repeating the last read
+        let table_rows =
+            preprocessed_table.aggregate_columns(&coefficients, SortOrder::TablePosition);
+        let mut f = MultiSet(self.rows.iter().map(|&row| table_rows.0[row]).collect());
+        let last_read = f.last();
+        f.extend(pad_by, last_read);
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(f.len()).unwrap();
+        let f_poly = f.to_polynomial(&domain);
+
+        // Fold the running commitments using the challenges
+        let padding_commitment = kzg10::sum_commitments(
+            self.lagrange_commitments[reads..reads + pad_by]
+                .iter()
+                .collect(),
+        );
+        let f_commit = kzg10::linear_combination_commitments(
+            vec![
+                &self.wire_commitments[0],
+                &self.wire_commitments[1],
+                &self.wire_commitments[2],
+                &padding_commitment,
+            ],
+            &[coefficients[0], coefficients[1], coefficients[2], last_read],
+        );
+
+        let (proof, _) = prove_aggregated::<KZG10Scheme, _, _>(
+            f,
+            f_poly,
+            f_commit,
+            reads,
+            preprocessed_table,
+            None,
+            &coefficients,
+            self.sort_order,
+            self.coset_shift,
+            proving_key,
+            transcript,
+        )?;
+        Ok(proof)
+    }
+}
+
+/// A lookup whose witness has been prepared ahead of time, so that it can be proven repeatedly,
+/// for example against different transcripts
+///
+/// The following do not depend on the Fiat-Shamir challenges, and are computed once in `LookUp::prepare`
+/// or `TwoOutputLookUp::prepare`:
+/// - each wire, padded to one less than the size of the table
+/// - the polynomial of each padded wire, and the commitment to it
+///
+/// The aggregated witness polynomial and its commitment are linear in the wires, so they are computed from the
+/// above using the aggregation coefficients. This needs no IFFT, and the commitment is a multi-scalar multiplication
+/// with one term for each wire commitment rather than a new commitment, which has one term for every coefficient of the polynomial.
+/// The order of the sorted table depends on the coefficients, so the aggregated table, h_1, h_2, Z(X)
+/// and the quotient polynomial are recomputed for every proof
+pub struct PreparedLookUp {
+    n: usize,
+    // The number of reads, before the wires were padded
+    reads: usize,
+    strategy: AggregationStrategy,
+    sort_order: SortOrder,
+    coset_shift: Fr,
+    wires: Vec<MultiSet>,
+    wire_polys: Vec<Polynomial<Fr>>,
+    wire_commitments: Vec<Commitment<Bls12_381>>,
+}
+
+impl<T: LookUpTable> LookUp<T> {
+    /// Pads the witness to the size of the table and commits to each wire,
+    /// so that the witness can be proven repeatedly
+    pub fn prepare(
+        &self,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+    ) -> Result<PreparedLookUp, LookUpError> {
+        PreparedLookUp::new(
+            vec![
+                self.left_wires.clone(),
+                self.right_wires.clone(),
+                self.output_wires.clone(),
+            ],
+            proving_key,
+            preprocessed_table.n,
+            self.strategy,
+            self.sort_order,
+            self.coset_shift,
+        )
+    }
+}
+
+impl PreparedLookUp {
+    // Pads each wire to one less than the size of a table with `n` rows, and commits to it
+    fn new(
+        mut wires: Vec<MultiSet>,
+        proving_key: &Powers<Bls12_381>,
+        n: usize,
+        strategy: AggregationStrategy,
+        sort_order: SortOrder,
+        coset_shift: Fr,
+    ) -> Result<PreparedLookUp, LookUpError> {
+        check_prover_setup(proving_key, n, coset_shift)?;
+        let reads = wires[0].len();
+        pad_wires(&mut wires.iter_mut().collect::<Vec<_>>(), n)?;
+
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(n - 1).unwrap();
+        let wire_polys: Vec<_> = wires
+            .iter()
+            .map(|wire| wire.to_polynomial(&domain))
+            .collect();
+        let wire_commitments = wire_polys
+            .iter()
+            .map(|poly| kzg10::commit(proving_key, poly))
+            .collect();
+
+        Ok(PreparedLookUp {
+            n,
+            reads,
+            strategy,
+            sort_order,
+            coset_shift,
+            wires,
+            wire_polys,
+            wire_commitments,
+        })
+    }
+    /// Creates a proof that the prepared witness is within the table
+    /// This is equal to the proof that `LookUp::prove` or `TwoOutputLookUp::prove` would create
+    /// for the same reads and transcript
+    pub fn prove<P: TableColumns>(
+        &self,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &P,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<MultiSetEqualityProof, LookUpError> {
+        if preprocessed_table.n() != self.n {
+            return Err(LookUpError::InvalidWitnessSize {
+                witness: self.n - 1,
+                table: preprocessed_table.n(),
+            });
+        }
+        check_column_count(self.wires.len(), preprocessed_table)?;
+        check_prover_setup(proving_key, self.n, self.coset_shift)?;
+
+        // Bind the size of the domain to the proof
+        transcript.append_scalar(labels::DOMAIN_SIZE, &Fr::from(self.n as u64));
+
+        // Generate the aggregation challenges
+        let coefficients = self.strategy.coefficients(transcript, self.wires.len());
+        self.sort_order.append_to_transcript(transcript);
+
+        // Aggregate the witness, its polynomial and its commitment using the challenges
+        let f = aggregate_wires(self.wires.iter().collect(), &coefficients);
+        let mut f_poly = Polynomial::zero();
+        for (poly, coefficient) in self.wire_polys.iter().zip(coefficients.iter()) {
+            let coeffs = poly.coeffs.iter().map(|c| *c * coefficient).collect();
+            f_poly += &Polynomial::from_coefficients_vec(coeffs);
+        }
+        let f_commit = kzg10::linear_combination_commitments(
+            self.wire_commitments.iter().collect(),
+            &coefficients,
+        );
+
+        let (proof, _) = prove_aggregated::<KZG10Scheme, _, _>(
+            f,
+            f_poly,
+            f_commit,
+            self.reads,
+            preprocessed_table,
+            None,
+            &coefficients,
+            self.sort_order,
+            self.coset_shift,
+            proving_key,
+            transcript,
+        )?;
+        Ok(proof)
+    }
+}
+
+/// Creates a proof that a single key is in the table
+/// The witness is made up of one read, which is then padded to the size of the table
+pub fn prove_single<T: LookUpTable>(
+    table: T,
+    proving_key: &Powers<Bls12_381>,
+    preprocessed_table: &PreProcessedTable,
+    key: &(Fr, Fr),
+    transcript: &mut dyn TranscriptProtocol,
+) -> Result<MultiSetEqualityProof, LookUpError> {
+    let mut lookup = LookUp::new(table);
+    if !lookup.read(key) {
+        return Err(LookUpError::KeyNotFound);
+    }
+    lookup.prove(proving_key, preprocessed_table, transcript)
+}
+
+// Creates a proof that the wires, one for each column of the table, are within the table
+// The wires are padded to one less than the size of the table, and then aggregated like the columns of the table
+#[allow(clippy::too_many_arguments)]
+fn prove_wires<PC: PolynomialCommitment, K: CommitKey<PC>, P: TableColumns<PC>>(
+    wires: &mut [&mut MultiSet],
+    preprocessed_table: &P,
+    cache: Option<&MergedTableCache>,
+    strategy: AggregationStrategy,
+    sort_order: SortOrder,
+    coset_shift: Fr,
+    commit_key: &K,
+    transcript: &mut dyn TranscriptProtocol,
+) -> Result<(MultiSetEqualityProof<PC>, Accumulator<PC>), LookUpError> {
+    check_column_count(wires.len(), preprocessed_table)?;
+    let n = preprocessed_table.n();
+    check_prover_setup(commit_key, n, coset_shift)?;
+
+    // Bind the size of the domain to the proof
+    transcript.append_scalar(labels::DOMAIN_SIZE, &Fr::from(n as u64));
+
+    // Generate the aggregation challenges
+    let coefficients = strategy.coefficients(transcript, wires.len());
+    sort_order.append_to_transcript(transcript);
+
+    // Aggregate the witness using the challenges, and commit to it
+    let (f, reads) = pad_and_aggregate(wires, &coefficients, n)?;
+    let domain: EvaluationDomain<Fr> = EvaluationDomain::new(f.len()).unwrap();
+    let f_poly = f.to_polynomial(&domain);
+    let f_commit = commit_key.commit(&f_poly);
+
+    prove_aggregated(
+        f,
+        f_poly,
+        f_commit,
+        reads,
+        preprocessed_table,
+        cache,
+        &coefficients,
+        sort_order,
+        coset_shift,
+        commit_key,
+        transcript,
+    )
+}
+
+// Creates the proof for the aggregated witness `f`, whose first `reads` values are reads and the rest padding,
+// against the table aggregated using `coefficients`, which is taken from `cache` if one is given
+// Every way of creating a lookup proof ends here, once the witness has been aggregated and committed to
+// With `SortOrder::TablePosition` the aggregated table is a linear combination of the columns, so its commitment is
+// computed from the column commitments
+#[allow(clippy::too_many_arguments)]
+fn prove_aggregated<PC: PolynomialCommitment, K: CommitKey<PC>, P: TableColumns<PC>>(
+    f: MultiSet,
+    f_poly: Polynomial<Fr>,
+    f_commit: PC::Commitment,
+    reads: usize,
+    preprocessed_table: &P,
+    cache: Option<&MergedTableCache>,
+    coefficients: &[Fr],
+    sort_order: SortOrder,
+    coset_shift: Fr,
+    commit_key: &K,
+    transcript: &mut dyn TranscriptProtocol,
+) -> Result<(MultiSetEqualityProof<PC>, Accumulator<PC>), LookUpError> {
+    let t = match cache {
+        Some(cache) => cache.aggregate_columns(preprocessed_table, coefficients, sort_order),
+        None => preprocessed_table.aggregate_columns(coefficients, sort_order),
+    };
+    if f.len() + 1 != t.len() {
+        return Err(LookUpError::InvalidWitnessSize {
+            witness: f.len(),
+            table: t.len(),
+        });
+    }
+    check_reads_in_table(&f, &t, reads)?;
+
+    let t_commit = match sort_order {
+        SortOrder::TablePosition => Some(preprocessed_table.aggregated_commitment(coefficients)),
+        SortOrder::Canonical => None,
+    };
+
+    // Create a Multi-set equality proof
+    Ok(multiset_equality::prove_with_witness_polynomial(
+        f,
+        f_poly,
+        f_commit,
+        t,
+        t_commit,
+        commit_key,
+        coset_shift,
+        sort_order,
+        transcript,
+    ))
+}
+
+// Returns an error unless there is one wire for each column of the table
+fn check_column_count<PC: PolynomialCommitment, P: TableColumns<PC>>(
+    wires: usize,
+    preprocessed_table: &P,
+) -> Result<(), LookUpError> {
+    let columns = preprocessed_table.columns().len();
+    if wires != columns {
+        return Err(LookUpError::ColumnCountMismatch {
+            witness: wires,
+            table: columns,
+        });
+    }
+    Ok(())
+}
+
+// Returns an error unless the commit key can commit to every polynomial created when proving against a table
+// with `n` rows, and the quotient polynomial can be computed on the coset given by `coset_shift`
+fn check_prover_setup<PC: PolynomialCommitment, K: CommitKey<PC>>(
+    commit_key: &K,
+    n: usize,
+    coset_shift: Fr,
+) -> Result<(), LookUpError> {
+    let degree = commit_key.max_degree();
+    let required = kzg10::required_setup_degree(n);
+    if degree < required {
+        return Err(LookUpError::SetupTooSmall { degree, required });
+    }
+    let domain: EvaluationDomain<Fr> = EvaluationDomain::new(n).unwrap();
+    if !quotient_poly::is_valid_coset_shift(&domain, coset_shift) {
+        return Err(LookUpError::InvalidCosetShift);
+    }
+    Ok(())
+}
+
+// Returns the indices of the reads whose aggregated value is not in the aggregated table
+// Only the first `reads` values of `f` are reads, the rest are padding which repeats the last read
+// A row which is not in the table aggregates to a value in it with negligible probability, so such a row is
+// reported here rather than making the grand product fail to return to one
+fn check_reads_in_table(f: &MultiSet, t: &MultiSet, reads: usize) -> Result<(), LookUpError> {
+    let table: HashSet<&Fr> = t.as_slice().iter().collect();
+    let missing: Vec<usize> = (0..reads).filter(|&i| !table.contains(&f.0[i])).collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(LookUpError::RowsNotInTable(missing))
+    }
+}
+
+// Pads the wires to one less than the size of a table with `n` rows, and aggregates them using a coefficient for each wire
+// Returns the aggregated witness and the number of reads before padding
+// A zero coefficient would collapse its wire, so it is rejected before anything is padded
+fn pad_and_aggregate(
+    wires: &mut [&mut MultiSet],
+    coefficients: &[Fr],
+    n: usize,
+) -> Result<(MultiSet, usize), LookUpError> {
+    if coefficients.iter().any(|coefficient| coefficient.is_zero()) {
+        return Err(LookUpError::ZeroChallenge);
+    }
+    let reads = wires[0].len();
+    pad_wires(wires, n)?;
+    let f = aggregate_wires(wires.iter().map(|wire| &**wire).collect(), coefficients);
+    Ok((f, reads))
+}
+
+// Aggregates the padded wires using a coefficient for each wire
+// Three wires, which is the common case, are combined in a single pass
+fn aggregate_wires(wires: Vec<&MultiSet>, coefficients: &[Fr]) -> MultiSet {
+    match (wires.as_slice(), coefficients) {
+        ([a, b, c], [k_a, k_b, k_c]) => MultiSet::linear_combination3(a, b, c, &[*k_a, *k_b, *k_c]),
+        _ => MultiSet::linear_combination(wires, coefficients),
+    }
+}
 
-        // Create a Multi-set equality proof
-        multiset_equality::prove(f, t, proving_key, transcript)
+// Pads every wire to one less than the size of a table with `n` rows, by repeating the last value of the wire
+// Nothing is padded if an error is returned
+fn pad_wires(wires: &mut [&mut MultiSet], n: usize) -> Result<(), LookUpError> {
+    let pad_by = padding(wires[0].len(), n)?;
+    for wire in wires.iter_mut() {
+        let last = wire.last();
+        wire.extend(pad_by, last);
+    }
+    Ok(())
+}
+
+// Returns the number of elements each wire must be padded by, so that `reads` reads are one less than a table with `n` rows
+fn padding(reads: usize, n: usize) -> Result<usize, LookUpError> {
+    if reads == 0 {
+        return Err(LookUpError::NoReads);
+    }
+    let capacity = n.saturating_sub(1);
+    capacity
+        .checked_sub(reads)
+        .ok_or(LookUpError::WitnessExceedsTable { reads, capacity })
+}
+
+/// A lookup into a table where each key maps to two outputs
+/// The witness has one column for each input and each output, which are aggregated like the columns of the table
+pub struct TwoOutputLookUp<T: TwoOutputLookUpTable> {
+    table: T,
+    left_wires: MultiSet,
+    right_wires: MultiSet,
+    output_wires: [MultiSet; 2],
+    // How the columns are combined into one multiset
+    strategy: AggregationStrategy,
+    // How the table is ordered, and how the witness is sorted by it
+    sort_order: SortOrder,
+    // The coset of the 4n domain that the quotient polynomial is computed on
+    coset_shift: Fr,
+}
+
+impl<T: TwoOutputLookUpTable> TwoOutputLookUp<T> {
+    pub fn new(table: T) -> TwoOutputLookUp<T> {
+        TwoOutputLookUp {
+            table,
+            left_wires: MultiSet::new(),
+            right_wires: MultiSet::new(),
+            output_wires: [MultiSet::new(), MultiSet::new()],
+            strategy: AggregationStrategy::default(),
+            sort_order: SortOrder::default(),
+            coset_shift: quotient_poly::default_coset_shift(),
+        }
+    }
+    /// Sets the strategy used to combine the columns of the table and witness
+    /// The verifier must use the same strategy, see `MultiSetEqualityProof::verify_with_options`
+    pub fn with_strategy(mut self, strategy: AggregationStrategy) -> TwoOutputLookUp<T> {
+        self.strategy = strategy;
+        self
+    }
+    /// Sets the order of the table, which the witness is sorted by
+    /// The verifier must use the same order, see `MultiSetEqualityProof::verify_with_options`
+    pub fn with_sort_order(mut self, sort_order: SortOrder) -> TwoOutputLookUp<T> {
+        self.sort_order = sort_order;
+        self
+    }
+    /// Sets the coset shift `k`, like `LookUp::with_coset_shift`
+    pub fn with_coset_shift(mut self, coset_shift: Fr) -> TwoOutputLookUp<T> {
+        self.coset_shift = coset_shift;
+        self
+    }
+    /// Reads both outputs from the underlying table and adds the key and outputs to the witness
+    /// Returns the outputs, or None if the key is not in the table, in which case nothing is added
+    pub fn read(&mut self, key: &(Fr, Fr)) -> Option<(Fr, Fr)> {
+        let output = *self.table.read(key)?;
+        self.left_wires.push(key.0);
+        self.right_wires.push(key.1);
+        self.output_wires[0].push(output.0);
+        self.output_wires[1].push(output.1);
+        Some(output)
+    }
+    /// Returns the number of values read so far
+    pub fn num_reads(&self) -> usize {
+        self.left_wires.len()
+    }
+
+    /// Aggregates the table and witness values into one multiset using a coefficient for each column
+    /// and pads the witness to be one less than the size of the table
+    /// The table is ordered using the sort order of the lookup
+    pub fn to_multiset(
+        &mut self,
+        preprocessed_table: &PreProcessedTwoOutputTable,
+        coefficients: &[Fr; 4],
+    ) -> Result<(MultiSet, MultiSet), LookUpError> {
+        // Pad the wires to be one less than the table multiset, and aggregate them
+        let [output_0, output_1] = &mut self.output_wires;
+        let (merged_witness, _) = pad_and_aggregate(
+            &mut [
+                &mut self.left_wires,
+                &mut self.right_wires,
+                output_0,
+                output_1,
+            ],
+            coefficients,
+            preprocessed_table.n,
+        )?;
+
+        let merged_table = preprocessed_table.aggregate_columns(coefficients, self.sort_order);
+
+        Ok((merged_witness, merged_table))
+    }
+
+    /// Creates a proof that every read, along with both of its outputs, is within the table
+    /// With the default options, the proof is verified with `MultiSetEqualityProof::verify_two_outputs`
+    pub fn prove(
+        &mut self,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &PreProcessedTwoOutputTable,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<MultiSetEqualityProof, LookUpError> {
+        let [output_0, output_1] = &mut self.output_wires;
+        let (proof, _) = prove_wires::<KZG10Scheme, _, _>(
+            &mut [
+                &mut self.left_wires,
+                &mut self.right_wires,
+                output_0,
+                output_1,
+            ],
+            preprocessed_table,
+            None,
+            self.strategy,
+            self.sort_order,
+            self.coset_shift,
+            proving_key,
+            transcript,
+        )?;
+        Ok(proof)
+    }
+    /// Pads the witness to the size of the table and commits to each wire,
+    /// so that the witness can be proven repeatedly, like `LookUp::prepare`
+    pub fn prepare(
+        &self,
+        proving_key: &Powers<Bls12_381>,
+        preprocessed_table: &PreProcessedTwoOutputTable,
+    ) -> Result<PreparedLookUp, LookUpError> {
+        PreparedLookUp::new(
+            vec![
+                self.left_wires.clone(),
+                self.right_wires.clone(),
+                self.output_wires[0].clone(),
+                self.output_wires[1].clone(),
+            ],
+            proving_key,
+            preprocessed_table.n,
+            self.strategy,
+            self.sort_order,
+            self.coset_shift,
+        )
     }
 }
 
@@ -107,6 +983,7 @@ impl<T: LookUpTable> LookUp<T> {
 mod test {
     use super::*;
     use crate::lookup_table::XOR4BitTable;
+    use crate::proof::VerificationError;
     use merlin::Transcript;
 
     #[test]
@@ -127,7 +1004,9 @@ mod test {
         // Add 3 XOR 5
         lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
 
-        let (f, t) = lookup.to_multiset(&preprocessed_table, Fr::from(5u8));
+        let (f, t) = lookup
+            .to_multiset(&preprocessed_table, Fr::from(5u8))
+            .unwrap();
         assert_eq!(f.len() + 1, t.len());
 
         assert!(t.len().is_power_of_two());
@@ -150,8 +1029,11 @@ mod test {
         // Add 3 XOR 5
         lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
 
-        let (f, t) = lookup.to_multiset(&preprocessed_table, Fr::from(5u8));
-        assert!(f.is_subset_of(&t));
+        let (f, t) = lookup
+            .to_multiset(&preprocessed_table, Fr::from(5u8))
+            .unwrap();
+        // The witness is padded by repeating the last read, so only membership of each element is checked
+        assert!(f.as_slice().iter().all(|x| t.contains(x)));
     }
     #[test]
     fn test_len() {
@@ -179,8 +1061,11 @@ mod test {
         assert_eq!(lookup.right_wires.len(), 1);
         assert_eq!(lookup.output_wires.len(), 1);
 
-        let (f, t) = lookup.to_multiset(&preprocessed_table, Fr::from(5u8));
-        assert!(f.is_subset_of(&t));
+        let (f, t) = lookup
+            .to_multiset(&preprocessed_table, Fr::from(5u8))
+            .unwrap();
+        // The witness is padded by repeating the last read, so only membership of each element is checked
+        assert!(f.as_slice().iter().all(|x| t.contains(x)));
     }
     #[test]
     fn test_proof() {
@@ -201,10 +1086,1159 @@ mod test {
         lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
 
         let mut prover_transcript = Transcript::new(b"lookup");
-        let proof = lookup.prove(&proving_key, &preprocessed_table, &mut prover_transcript);
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_prove_single() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        // 16 is not a 4-bit value, so there is nothing to prove
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = prove_single(
+            XOR4BitTable::new(),
+            &proving_key,
+            &preprocessed_table,
+            &(Fr::from(16u8), Fr::from(6u8)),
+            &mut prover_transcript,
+        );
+        assert_eq!(proof.err(), Some(LookUpError::KeyNotFound));
+
+        // Prove 7 XOR 9
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = prove_single(
+            table,
+            &proving_key,
+            &preprocessed_table,
+            &(Fr::from(7u8), Fr::from(9u8)),
+            &mut prover_transcript,
+        )
+        .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_proof_full_witness() {
+        // The largest witness that we can prove has one element less than the table
+        // so no padding is needed
+
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let n = table.len();
+        let preprocessed_table = table.preprocess(&proving_key, n);
+
+        let mut lookup = LookUp::new(table);
+        for i in 0..n - 1 {
+            let added = lookup.read(&(Fr::from((i / 16) as u8), Fr::from((i % 16) as u8)));
+            assert!(added);
+        }
+        assert_eq!(lookup.left_wires.len(), n - 1);
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
 
         let mut verifier_transcript = Transcript::new(b"lookup");
-        let ok = proof.verify(&verifier_key, &preprocessed_table, &mut verifier_transcript);
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
         assert!(ok);
     }
+    #[test]
+    fn test_proof_invalid_witness_size() {
+        // Setup SRS
+        let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        // A table whose declared size does not match its columns
+        let inconsistent_table = PreProcessedTable {
+            n: 2usize.pow(7),
+            t_1: preprocessed_table.t_1.clone(),
+            t_2: preprocessed_table.t_2.clone(),
+            t_3: preprocessed_table.t_3.clone(),
+        };
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let result = lookup.prove(&proving_key, &inconsistent_table, &mut prover_transcript);
+        assert_eq!(
+            result.err(),
+            Some(LookUpError::InvalidWitnessSize {
+                witness: 2usize.pow(7) - 1,
+                table: 2usize.pow(8),
+            })
+        );
+    }
+    #[test]
+    fn test_no_reads() {
+        let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+        assert_eq!(
+            lookup.to_multiset(&preprocessed_table, Fr::from(5u8)).err(),
+            Some(LookUpError::NoReads)
+        );
+        assert_eq!(
+            lookup.prepare(&proving_key, &preprocessed_table).err(),
+            Some(LookUpError::NoReads)
+        );
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let result = lookup.prove(&proving_key, &preprocessed_table, &mut prover_transcript);
+        assert_eq!(result.err(), Some(LookUpError::NoReads));
+    }
+    #[test]
+    fn test_witness_exceeds_table() {
+        let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let n = 2usize.pow(8);
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, n);
+
+        // Every row of the table is read, which is one more than can be proven
+        let mut lookup = LookUp::new(table);
+        for i in 0..n {
+            let added = lookup.read(&(Fr::from((i / 16) as u8), Fr::from((i % 16) as u8)));
+            assert!(added);
+        }
+
+        let expected = LookUpError::WitnessExceedsTable {
+            reads: n,
+            capacity: n - 1,
+        };
+        assert_eq!(
+            lookup.prepare(&proving_key, &preprocessed_table).err(),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            lookup.to_multiset(&preprocessed_table, Fr::from(5u8)).err(),
+            Some(expected.clone())
+        );
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let result = lookup.prove(&proving_key, &preprocessed_table, &mut prover_transcript);
+        assert_eq!(result.err(), Some(expected));
+    }
+    #[test]
+    fn test_two_output_proof() {
+        use crate::lookup_table::Add4BitTable;
+
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = Add4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = TwoOutputLookUp::new(table);
+
+        // 9 + 8 = 16 + 1
+        let output = lookup.read(&(Fr::from(9u8), Fr::from(8u8)));
+        assert_eq!(output, Some((Fr::from(1u8), Fr::from(1u8))));
+        // 3 + 4 = 7
+        let output = lookup.read(&(Fr::from(3u8), Fr::from(4u8)));
+        assert_eq!(output, Some((Fr::from(7u8), Fr::zero())));
+        // 15 + 15 = 16 + 14
+        let output = lookup.read(&(Fr::from(15u8), Fr::from(15u8)));
+        assert_eq!(output, Some((Fr::from(14u8), Fr::one())));
+        // 16 is not a 4 bit value, so it is not added to the witness
+        assert_eq!(lookup.read(&(Fr::from(16u8), Fr::from(1u8))), None);
+        assert_eq!(lookup.num_reads(), 3);
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify_two_outputs(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_two_output_proof_is_bound_to_table() {
+        use crate::lookup_table::Add4BitTable;
+
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = Add4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        // A proof for a table with one output, of the same size
+        let xor_table = XOR4BitTable::new();
+        let preprocessed_xor_table = xor_table.preprocess(&proving_key, 2usize.pow(8));
+        let mut xor_lookup = LookUp::new(xor_table);
+        xor_lookup.read(&(Fr::from(9u8), Fr::from(8u8)));
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let xor_proof = xor_lookup
+            .prove(
+                &proving_key,
+                &preprocessed_xor_table,
+                &mut prover_transcript,
+            )
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = xor_proof.verify_two_outputs(
+            &verifier_key,
+            &preprocessed_table,
+            &mut verifier_transcript,
+        );
+        assert_eq!(result, Err(VerificationError::TableCommitmentMismatch));
+
+        // A table where 9 + 8 has no carry
+        let mut columns = table.to_multiset();
+        let index = (0..columns[0].len())
+            .find(|&i| columns[0].0[i] == Fr::from(9u8) && columns[1].0[i] == Fr::from(8u8))
+            .unwrap();
+        columns[3].0[index] = Fr::zero();
+        let wrong_carry_table =
+            PreProcessedTwoOutputTable::new(&proving_key, 2usize.pow(8), columns);
+
+        // Read 9 + 8 with the wrong carry
+        let mut lookup = TwoOutputLookUp::new(table);
+        lookup.left_wires.push(Fr::from(9u8));
+        lookup.right_wires.push(Fr::from(8u8));
+        lookup.output_wires[0].push(Fr::from(1u8));
+        lookup.output_wires[1].push(Fr::zero());
+
+        // The read is in the other table, so the proof for it is honest, but it is rejected against this table
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &wrong_carry_table, &mut prover_transcript)
+            .unwrap();
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result =
+            proof.verify_two_outputs(&verifier_key, &preprocessed_table, &mut verifier_transcript);
+        assert_eq!(result, Err(VerificationError::TableCommitmentMismatch));
+    }
+    #[test]
+    fn test_two_output_options() {
+        use crate::lookup_table::Add4BitTable;
+
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = Add4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let strategy = AggregationStrategy::IndependentChallenges;
+        let mut lookup = TwoOutputLookUp::new(table)
+            .with_strategy(strategy)
+            .with_sort_order(SortOrder::Canonical);
+        lookup.read(&(Fr::from(9u8), Fr::from(8u8)));
+        lookup.read(&(Fr::from(3u8), Fr::from(4u8)));
+
+        let prepared = lookup.prepare(&proving_key, &preprocessed_table).unwrap();
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let prepared_proof = prepared
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+        assert_eq!(prepared_proof, proof);
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify_with_options(
+                &verifier_key,
+                &preprocessed_table,
+                strategy,
+                SortOrder::Canonical,
+                &mut verifier_transcript,
+            )
+            .is_ok();
+        assert!(ok);
+
+        // The verifier must use the same options
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify_two_outputs(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(!ok);
+
+        // A prepared witness for a table with two outputs can not be proven against a table with one output
+        let xor_table = XOR4BitTable::new().preprocess(&proving_key, 2usize.pow(8));
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let result = prepared.prove(&proving_key, &xor_table, &mut prover_transcript);
+        assert_eq!(
+            result.err(),
+            Some(LookUpError::ColumnCountMismatch {
+                witness: 4,
+                table: 3
+            })
+        );
+    }
+    #[test]
+    fn test_proof_packed_keys() {
+        use crate::lookup_table::{pack_key, PackedXOR4BitTable};
+
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = PackedXOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+
+        // Adds 1 XOR 2
+        assert!(lookup.read(&(pack_key(Fr::from(1u8), Fr::from(2u8), 4), Fr::zero())));
+        // Adds 2 XOR 4
+        assert!(lookup.read(&(pack_key(Fr::from(2u8), Fr::from(4u8), 4), Fr::zero())));
+        // Unpacked keys are not in the table
+        assert!(!lookup.read(&(Fr::from(3u8), Fr::from(5u8))));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_zero_challenge() {
+        // Setup SRS
+        let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+
+        let result = lookup.to_multiset(&preprocessed_table, Fr::zero());
+        assert_eq!(result.err(), Some(LookUpError::ZeroChallenge));
+    }
+    #[test]
+    fn test_prove_cached() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+        let cache = MergedTableCache::new();
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(2u8), Fr::from(4u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let uncached_proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        // The first proof fills the cache, and the second proof, with the same coefficients, reads from it
+        let mut proofs = Vec::new();
+        for _ in 0..2 {
+            let mut prover_transcript = Transcript::new(b"lookup");
+            let proof = lookup
+                .prove_cached(
+                    &proving_key,
+                    &preprocessed_table,
+                    &cache,
+                    &mut prover_transcript,
+                )
+                .unwrap();
+            proofs.push(proof);
+        }
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(proofs[0], uncached_proof);
+        assert_eq!(proofs[1], uncached_proof);
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proofs[1]
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        // Another transcript gives other coefficients, so the table is aggregated again
+        let mut prover_transcript = Transcript::new(b"other_lookup");
+        lookup
+            .prove_cached(
+                &proving_key,
+                &preprocessed_table,
+                &cache,
+                &mut prover_transcript,
+            )
+            .unwrap();
+        assert_eq!(cache.hits(), 1);
+    }
+    #[test]
+    fn test_streaming_proof() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let keys = vec![
+            (Fr::from(1u8), Fr::from(2u8)),
+            (Fr::from(2u8), Fr::from(4u8)),
+            (Fr::from(16u8), Fr::from(4u8)),
+            (Fr::from(3u8), Fr::from(5u8)),
+            (Fr::from(15u8), Fr::from(15u8)),
+        ];
+
+        // Batch path
+        let mut lookup = LookUp::new(XOR4BitTable::new());
+        for key in keys.iter() {
+            lookup.read(key);
+        }
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let batch_proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        // Streaming path, using two chunks
+        let mut streaming_lookup =
+            StreamingLookUp::new(table, &proving_key, &preprocessed_table).unwrap();
+        let added = streaming_lookup.absorb_chunk(&keys[..2]);
+        assert_eq!(added, Ok(2));
+        // (16, 4) is not in the table
+        let added = streaming_lookup.absorb_chunk(&keys[2..]);
+        assert_eq!(added, Ok(2));
+        assert_eq!(streaming_lookup.num_reads(), 4);
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let streaming_proof = streaming_lookup
+            .finalize(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        assert_eq!(batch_proof, streaming_proof);
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = streaming_proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        // The reads are found in the table the lookup was created for, so they can not be proven against another
+        let (t_1, t_2, mut t_3) = XOR4BitTable::new().to_multiset();
+        t_3.0[0] = t_3.0[0] + Fr::one();
+        let other_table = PreProcessedTable::new(&proving_key, 2usize.pow(8), t_1, t_2, t_3);
+        let mut streaming_lookup =
+            StreamingLookUp::new(XOR4BitTable::new(), &proving_key, &preprocessed_table).unwrap();
+        streaming_lookup.absorb_chunk(&keys).unwrap();
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let result = streaming_lookup.finalize(&proving_key, &other_table, &mut prover_transcript);
+        assert_eq!(result.err(), Some(LookUpError::TableMismatch));
+    }
+    #[test]
+    fn test_streaming_capacity() {
+        // Setup SRS
+        let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+        let key = (Fr::from(1u8), Fr::from(2u8));
+
+        // A table of size 2^8 can prove 2^8 - 1 reads
+        let mut streaming_lookup =
+            StreamingLookUp::new(table, &proving_key, &preprocessed_table).unwrap();
+        let added = streaming_lookup.absorb_chunk(&vec![key; 254]);
+        assert_eq!(added, Ok(254));
+
+        // A chunk which does not fit is not absorbed
+        let added = streaming_lookup.absorb_chunk(&[key, key]);
+        assert_eq!(
+            added,
+            Err(LookUpError::WitnessExceedsTable {
+                reads: 256,
+                capacity: 255
+            })
+        );
+        assert_eq!(streaming_lookup.num_reads(), 254);
+
+        let added = streaming_lookup.absorb_chunk(&[key]);
+        assert_eq!(added, Ok(1));
+    }
+    #[cfg(feature = "record")]
+    #[test]
+    fn test_prove_recorded() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let (proof, ops) = lookup
+            .prove_recorded(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let labels: Vec<&str> = ops
+            .iter()
+            .map(|op| std::str::from_utf8(op.label()).unwrap())
+            .collect();
+        let expected_labels = vec![
+            "domain_size",
+            "alpha",
+            "alpha",
+            "h_1_poly",
+            "h_2_poly",
+            "beta",
+            "gamma",
+            "accumulator_poly",
+            "quotient_poly",
+            "evaluation_challenge",
+            "evaluation_challenge",
+            "f_eval",
+            "t_eval",
+            "h_1_eval",
+            "h_2_eval",
+            "z_eval",
+            "q_eval",
+            "t_omega_eval",
+            "h_1_omega_eval",
+            "h_2_omega_eval",
+            "z_omega_eval",
+            "witness_aggregation",
+        ];
+        assert_eq!(labels, expected_labels);
+        assert_eq!(
+            ops[1],
+            TranscriptOp::Challenge {
+                label: labels::ALPHA
+            }
+        );
+        assert_eq!(
+            ops[2],
+            TranscriptOp::AppendScalar {
+                label: labels::ALPHA,
+                len: 32
+            }
+        );
+
+        // Recording does not change the proof
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_proof_domain_size_mismatch() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let prover_table = table.preprocess(&proving_key, 2usize.pow(8));
+        let verifier_table = table.preprocess(&proving_key, 2usize.pow(9));
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &prover_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = proof.verify(&verifier_key, &verifier_table, &mut verifier_transcript);
+        assert_eq!(
+            result,
+            Err(VerificationError::DomainSizeMismatch {
+                proof: 2usize.pow(8),
+                table: 2usize.pow(9),
+            })
+        );
+    }
+    #[test]
+    fn test_from_wires() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        // Wires for 1 XOR 2, 2 XOR 4 and 3 XOR 5
+        let left = MultiSet(vec![Fr::from(1u8), Fr::from(2u8), Fr::from(3u8)]);
+        let right = MultiSet(vec![Fr::from(2u8), Fr::from(4u8), Fr::from(5u8)]);
+        let output = MultiSet(vec![Fr::from(3u8), Fr::from(6u8), Fr::from(6u8)]);
+
+        // Wires must be of equal length
+        let result = LookUp::from_wires(
+            XOR4BitTable::new(),
+            left.clone(),
+            right.clone(),
+            MultiSet(vec![Fr::from(3u8)]),
+        );
+        assert_eq!(result.err(), Some(LookUpError::WireLengthMismatch));
+
+        // 2 XOR 4 is not 7
+        let bad_output = MultiSet(vec![Fr::from(3u8), Fr::from(7u8), Fr::from(6u8)]);
+        let result =
+            LookUp::from_wires(XOR4BitTable::new(), left.clone(), right.clone(), bad_output);
+        assert_eq!(result.err(), Some(LookUpError::RowsNotInTable(vec![1])));
+
+        let mut lookup = LookUp::from_wires(table, left, right, output).unwrap();
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_verify_with_table_columns() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let n = 2usize.pow(8);
+        let preprocessed_table = table.preprocess(&proving_key, n);
+        let (t_1, t_2, t_3) = table.to_multiset();
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = proof.verify_with_table_columns(
+            &verifier_key,
+            t_1.clone(),
+            t_2.clone(),
+            t_3.clone(),
+            n,
+            &mut verifier_transcript,
+        );
+        assert!(result.is_ok());
+
+        // The columns may be given in any order of their rows, as they are pre-processed by the verifier
+        let order: Vec<usize> = (0..t_1.len()).rev().collect();
+        let reverse = |column: &MultiSet| MultiSet(order.iter().map(|&i| column.0[i]).collect());
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = proof.verify_with_table_columns(
+            &verifier_key,
+            reverse(&t_1),
+            reverse(&t_2),
+            reverse(&t_3),
+            n,
+            &mut verifier_transcript,
+        );
+        assert!(result.is_ok());
+
+        // Changing a single output in the table is caught
+        let mut bad_t_3 = t_3;
+        bad_t_3.0[0] = bad_t_3.0[0] + Fr::from(1u8);
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = proof.verify_with_table_columns(
+            &verifier_key,
+            t_1.clone(),
+            t_2.clone(),
+            bad_t_3.clone(),
+            n,
+            &mut verifier_transcript,
+        );
+        assert_eq!(result, Err(VerificationError::TableCommitmentMismatch));
+
+        // It is also caught by `verify`, using only the column commitments of the preprocessed table
+        let bad_table = PreProcessedTable::new(&proving_key, n, t_1, t_2, bad_t_3);
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = proof.verify(&verifier_key, &bad_table, &mut verifier_transcript);
+        assert_eq!(result, Err(VerificationError::TableCommitmentMismatch));
+    }
+    #[test]
+    fn test_aggregation_strategies() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let strategies = vec![
+            AggregationStrategy::Powers,
+            AggregationStrategy::IndependentChallenges,
+        ];
+
+        for strategy in strategies {
+            let mut lookup = LookUp::new(XOR4BitTable::new()).with_strategy(strategy);
+            lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+            lookup.read(&(Fr::from(2u8), Fr::from(4u8)));
+            lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
+
+            let mut prover_transcript = Transcript::new(b"lookup");
+            let proof = lookup
+                .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+                .unwrap();
+
+            let mut verifier_transcript = Transcript::new(b"lookup");
+            let result = proof.verify_with_strategy(
+                &verifier_key,
+                &preprocessed_table,
+                strategy,
+                &mut verifier_transcript,
+            );
+            assert!(result.is_ok());
+        }
+
+        // A proof is rejected by a verifier expecting a different strategy
+        let mut lookup =
+            LookUp::new(table).with_strategy(AggregationStrategy::IndependentChallenges);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = proof.verify(&verifier_key, &preprocessed_table, &mut verifier_transcript);
+        assert!(result.is_err());
+    }
+    #[test]
+    fn test_forged_row_is_rejected() {
+        // Setup SRS
+        let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        // (17, 1, 3) is not in the table, but with powers of 16 it would aggregate to the same value as (1, 2, 3)
+        // The coefficients are challenges, so it is rejected with every strategy
+        for strategy in vec![
+            AggregationStrategy::Powers,
+            AggregationStrategy::IndependentChallenges,
+        ] {
+            let mut lookup = LookUp::new(XOR4BitTable::new()).with_strategy(strategy);
+            lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+            lookup.left_wires.push(Fr::from(17u8));
+            lookup.right_wires.push(Fr::from(1u8));
+            lookup.output_wires.push(Fr::from(3u8));
+            assert_eq!(lookup.validate_rows(), Err(vec![1]));
+
+            let mut prover_transcript = Transcript::new(b"lookup");
+            let result = lookup.prove(&proving_key, &preprocessed_table, &mut prover_transcript);
+            assert_eq!(result.err(), Some(LookUpError::RowsNotInTable(vec![1])));
+
+            let prepared = lookup.prepare(&proving_key, &preprocessed_table).unwrap();
+            let mut prover_transcript = Transcript::new(b"lookup");
+            let result = prepared.prove(&proving_key, &preprocessed_table, &mut prover_transcript);
+            assert_eq!(result.err(), Some(LookUpError::RowsNotInTable(vec![1])));
+        }
+    }
+    #[test]
+    fn test_sort_orders() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let prove_with_order = |sort_order: SortOrder| {
+            let mut lookup = LookUp::new(XOR4BitTable::new()).with_sort_order(sort_order);
+            lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+            lookup.read(&(Fr::from(2u8), Fr::from(4u8)));
+            lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
+
+            let mut prover_transcript = Transcript::new(b"lookup");
+            lookup
+                .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+                .unwrap()
+        };
+        let verify_with_order = |proof: &MultiSetEqualityProof, sort_order: SortOrder| {
+            let mut verifier_transcript = Transcript::new(b"lookup");
+            proof.verify_with_sort_order(
+                &verifier_key,
+                &preprocessed_table,
+                sort_order,
+                &mut verifier_transcript,
+            )
+        };
+
+        let canonical = prove_with_order(SortOrder::Canonical);
+        let table_position = prove_with_order(SortOrder::TablePosition);
+        assert_ne!(canonical.commitments.t, table_position.commitments.t);
+
+        // Each proof is accepted by a verifier expecting the same order
+        assert!(verify_with_order(&canonical, SortOrder::Canonical).is_ok());
+        assert!(verify_with_order(&table_position, SortOrder::TablePosition).is_ok());
+
+        // A proof is rejected by a verifier expecting the other order
+        assert!(verify_with_order(&canonical, SortOrder::TablePosition).is_err());
+        assert!(verify_with_order(&table_position, SortOrder::Canonical).is_err());
+        // The default verifier expects the canonical order
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result =
+            table_position.verify(&verifier_key, &preprocessed_table, &mut verifier_transcript);
+        assert!(result.is_err());
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = canonical
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        // With either order, the proof is bound to the columns of the table
+        let (t_1, t_2, mut t_3) = XOR4BitTable::new().to_multiset();
+        let index = (0..t_1.len())
+            .find(|&i| t_1.0[i].is_zero() && t_2.0[i].is_zero())
+            .unwrap();
+        t_3.0[index] = Fr::one();
+        let other_table = PreProcessedTable::new(&proving_key, 2usize.pow(8), t_1, t_2, t_3);
+        for (proof, sort_order) in &[
+            (&canonical, SortOrder::Canonical),
+            (&table_position, SortOrder::TablePosition),
+        ] {
+            let mut verifier_transcript = Transcript::new(b"lookup");
+            let result = proof.verify_with_sort_order(
+                &verifier_key,
+                &other_table,
+                *sort_order,
+                &mut verifier_transcript,
+            );
+            assert_eq!(result, Err(VerificationError::TableCommitmentMismatch));
+        }
+    }
+    #[test]
+    fn test_accumulator_boundary_values() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(2u8), Fr::from(4u8)));
+        lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let (proof, accumulator) = lookup
+            .prove_with_accumulator(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        // Z(X) has one value for every element in the domain
+        // It starts at 1 and returns to 1 at the last element
+        assert_eq!(accumulator.evaluations.len(), preprocessed_table.n);
+        assert_eq!(accumulator.evaluations[0], Fr::one());
+        assert_eq!(*accumulator.evaluations.last().unwrap(), Fr::one());
+        assert_eq!(accumulator.commitment, proof.commitments.z);
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_kzg10_through_commitment_scheme() {
+        use crate::commitment::KZG10Scheme;
+
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let new_lookup = || {
+            let mut lookup = LookUp::new(XOR4BitTable::new());
+            lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+            lookup.read(&(Fr::from(7u8), Fr::from(9u8)));
+            lookup
+        };
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = new_lookup()
+            .prove_with_scheme::<KZG10Scheme, _>(
+                &proving_key,
+                &preprocessed_table,
+                &mut prover_transcript,
+            )
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify_with_scheme(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        // The proof is the same as the one created without going through the trait
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let kzg_proof = new_lookup()
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+        assert_eq!(proof, kzg_proof);
+
+        // A proof for a table of the same size where 0 XOR 0 is 1 is rejected,
+        // as the aggregated table is checked against the column commitments through the trait
+        let (t_1, t_2, mut t_3) = XOR4BitTable::new().to_multiset();
+        let index = (0..t_1.len())
+            .find(|&i| t_1.0[i].is_zero() && t_2.0[i].is_zero())
+            .unwrap();
+        t_3.0[index] = Fr::one();
+        let other_table = PreProcessedTable::<KZG10Scheme>::with_scheme(
+            &proving_key,
+            2usize.pow(8),
+            t_1,
+            t_2,
+            t_3,
+        );
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let other_proof = new_lookup()
+            .prove_with_scheme(&proving_key, &other_table, &mut prover_transcript)
+            .unwrap();
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = other_proof
+            .verify_with_scheme(&verifier_key, &other_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = other_proof.verify_with_scheme(
+            &verifier_key,
+            &preprocessed_table,
+            &mut verifier_transcript,
+        );
+        assert_eq!(result, Err(VerificationError::TableCommitmentMismatch));
+    }
+    #[cfg(feature = "record")]
+    #[test]
+    fn test_prover_and_verifier_labels_match() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let (proof, prover_ops) = lookup
+            .prove_recorded(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let mut recorder = RecordingTranscript::new(&mut verifier_transcript);
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut recorder)
+            .is_ok();
+        assert!(ok);
+
+        // The verifier performs the same operations, with the same labels, as the prover
+        assert_eq!(recorder.into_ops(), prover_ops);
+    }
+    #[test]
+    fn test_proof_coset_shift() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let keys = vec![
+            (Fr::from(1u8), Fr::from(2u8)),
+            (Fr::from(2u8), Fr::from(4u8)),
+            (Fr::from(3u8), Fr::from(5u8)),
+        ];
+
+        // The proof does not depend on which coset the quotient was computed on
+        let mut proofs = Vec::new();
+        for coset_shift in vec![quotient_poly::default_coset_shift(), Fr::from(5u8)] {
+            let mut lookup = LookUp::new(XOR4BitTable::new()).with_coset_shift(coset_shift);
+            for key in keys.iter() {
+                lookup.read(key);
+            }
+
+            let mut prover_transcript = Transcript::new(b"lookup");
+            let proof = lookup
+                .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+                .unwrap();
+
+            let mut verifier_transcript = Transcript::new(b"lookup");
+            let ok = proof
+                .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+                .is_ok();
+            assert!(ok);
+            proofs.push(proof);
+        }
+        assert_eq!(proofs[0], proofs[1]);
+
+        // A shift whose coset intersects the domain is rejected before proving
+        let domain_4n: EvaluationDomain<Fr> = EvaluationDomain::new(4 * 2usize.pow(8)).unwrap();
+        for coset_shift in vec![Fr::one(), Fr::zero(), domain_4n.group_gen] {
+            let mut lookup = LookUp::new(XOR4BitTable::new()).with_coset_shift(coset_shift);
+            for key in keys.iter() {
+                lookup.read(key);
+            }
+
+            let mut prover_transcript = Transcript::new(b"lookup");
+            let result = lookup.prove(&proving_key, &preprocessed_table, &mut prover_transcript);
+            assert_eq!(result.err(), Some(LookUpError::InvalidCosetShift));
+            let result = lookup.prepare(&proving_key, &preprocessed_table);
+            assert_eq!(result.err(), Some(LookUpError::InvalidCosetShift));
+        }
+    }
+    #[test]
+    fn test_prepared_proof() {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(2u8), Fr::from(4u8)));
+        lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
+
+        let prepared = lookup.prepare(&proving_key, &preprocessed_table).unwrap();
+
+        // Prove the same witness against two different transcripts
+        for label in vec![b"lookup", b"other_"] {
+            let mut prover_transcript = Transcript::new(label);
+            let proof = prepared
+                .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+                .unwrap();
+
+            let mut verifier_transcript = Transcript::new(label);
+            let ok = proof
+                .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+                .is_ok();
+            assert!(ok);
+        }
+
+        // The prepared proof is the same as the proof created without preparing
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let prepared_proof = prepared
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+        assert_eq!(prepared_proof, proof);
+    }
+    #[test]
+    fn test_to_multiset_distinct_rows() {
+        // Setup SRS
+        let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        // The row (1, 2, 3) is repeated in the table
+        let (mut t_1, mut t_2, mut t_3) = XOR4BitTable::new().to_multiset();
+        t_1.push(Fr::from(1u8));
+        t_2.push(Fr::from(2u8));
+        t_3.push(Fr::from(3u8));
+        let preprocessed_table = PreProcessedTable::new(&proving_key, 2usize.pow(9), t_1, t_2, t_3);
+
+        let reads = [
+            (Fr::from(1u8), Fr::from(2u8)),
+            (Fr::from(3u8), Fr::from(5u8)),
+        ];
+        let mut lookup = LookUp::new(XOR4BitTable::new());
+        for key in reads.iter() {
+            lookup.read(key);
+        }
+
+        let alpha = Fr::from(100u8);
+        let (f, t) = lookup.to_multiset(&preprocessed_table, alpha).unwrap();
+        let (f_distinct, t_distinct) = lookup
+            .to_multiset_distinct_rows(&preprocessed_table, alpha)
+            .unwrap();
+
+        // Only the table changes, and the repeated row occurs once in it
+        assert_eq!(f, f_distinct);
+        let repeated_row = Fr::from(1u8) + alpha * Fr::from(2u8) + alpha * alpha * Fr::from(3u8);
+        let count = |set: &MultiSet| set.0.iter().filter(|&&x| x == repeated_row).count();
+        assert_eq!(count(&t), 2);
+        assert_eq!(count(&t_distinct), 1);
+        assert_eq!(t_distinct.len(), 2usize.pow(9));
+        assert!(t.0.iter().all(|x| t_distinct.contains(x)));
+        assert_eq!(t_distinct, t_distinct.sort());
+
+        // The distinct table is ordered using the sort order of the lookup
+        let mut lookup = LookUp::new(XOR4BitTable::new()).with_sort_order(SortOrder::TablePosition);
+        for key in reads.iter() {
+            lookup.read(key);
+        }
+        let (_, t_position) = lookup
+            .to_multiset_distinct_rows(&preprocessed_table, alpha)
+            .unwrap();
+        assert_eq!(
+            t_position,
+            preprocessed_table.merged_table_distinct(
+                &[Fr::one(), alpha, alpha * alpha],
+                SortOrder::TablePosition
+            )
+        );
+        assert_ne!(t_position, t_distinct);
+        assert_eq!(t_position.sort(), t_distinct);
+    }
+    #[test]
+    fn test_read_checked() {
+        let mut lookup = LookUp::new(XOR4BitTable::new());
+
+        assert_eq!(
+            lookup.read_checked(&(Fr::from(8u8), Fr::from(10u8))),
+            ReadResult::Found(Fr::from(2u8))
+        );
+        assert_eq!(
+            lookup.read_checked(&(Fr::from(16u8), Fr::from(6u8))),
+            ReadResult::OutOfDomain
+        );
+
+        // Only the key which was found is added to the witness
+        assert_eq!(lookup.left_wires, MultiSet::from_u64s(&[8]));
+        assert_eq!(lookup.right_wires, MultiSet::from_u64s(&[10]));
+        assert_eq!(lookup.output_wires, MultiSet::from_u64s(&[2]));
+    }
+    #[test]
+    fn test_validate_rows() {
+        let mut lookup = LookUp::new(XOR4BitTable::new());
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(2u8), Fr::from(4u8)));
+        lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
+        assert_eq!(lookup.validate_rows(), Ok(()));
+
+        // 2 XOR 4 is not 7
+        lookup.output_wires.0[1] = Fr::from(7u8);
+        assert_eq!(lookup.validate_rows(), Err(vec![1]));
+    }
 }
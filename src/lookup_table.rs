@@ -1,15 +1,394 @@
+use crate::commitment::{CommitKey, KZG10Scheme, PolynomialCommitment};
 use crate::kzg10;
-use crate::multiset::MultiSet;
+use crate::multiset::{to_u64, MultiSet};
+use crate::transcript::{labels, TranscriptProtocol};
 use algebra::bls12_381::Fr;
 use algebra::Bls12_381;
+use ff_fft::EvaluationDomain;
+use num_traits::identities::{One, Zero};
 use poly_commit::kzg10::{Commitment, Powers};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
-pub struct PreProcessedTable {
+/// The columns of a table, padded to a size `n`, along with the commitment to each column
+/// The columns are committed to using the commitment scheme `PC`, which is KZG10 unless stated otherwise
+pub struct PreProcessedTable<PC: PolynomialCommitment = KZG10Scheme> {
     pub n: usize,
-    pub t_1: (MultiSet, Commitment<Bls12_381>),
-    pub t_2: (MultiSet, Commitment<Bls12_381>),
-    pub t_3: (MultiSet, Commitment<Bls12_381>),
+    pub t_1: (MultiSet, PC::Commitment),
+    pub t_2: (MultiSet, PC::Commitment),
+    pub t_3: (MultiSet, PC::Commitment),
+}
+
+impl PreProcessedTable {
+    /// Pre-process the columns of a table by padding them to a size `n` and committing to each column
+    /// The rows are ordered by their values before padding, so the pre-processed table does not depend on
+    /// the order that the rows were given in. Equal rows are adjacent in the pre-processed table
+    /// Each commitment is to the polynomial whose evaluations over the domain are the column
+    pub fn new(
+        commit_key: &Powers<Bls12_381>,
+        n: usize,
+        t_1: MultiSet,
+        t_2: MultiSet,
+        t_3: MultiSet,
+    ) -> PreProcessedTable {
+        PreProcessedTable::with_scheme(commit_key, n, t_1, t_2, t_3)
+    }
+}
+
+impl<PC: PolynomialCommitment> PreProcessedTable<PC> {
+    /// Pre-process the columns of a table like `new`, committing to each column using the commitment scheme `PC`
+    pub fn with_scheme<K: CommitKey<PC>>(
+        commit_key: &K,
+        n: usize,
+        t_1: MultiSet,
+        t_2: MultiSet,
+        t_3: MultiSet,
+    ) -> PreProcessedTable<PC> {
+        let [t_1, t_2, t_3] = order_and_pad(n, [t_1, t_2, t_3]);
+
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(n).unwrap();
+        let t_1_commit = commit_key.commit(&t_1.to_polynomial(&domain));
+        let t_2_commit = commit_key.commit(&t_2.to_polynomial(&domain));
+        let t_3_commit = commit_key.commit(&t_3.to_polynomial(&domain));
+
+        PreProcessedTable {
+            n: n,
+            t_1: (t_1, t_1_commit),
+            t_2: (t_2, t_2_commit),
+            t_3: (t_3, t_3_commit),
+        }
+    }
+    /// Restricts the table to the first `smaller_n` rows and commits to the truncated columns with `commit_key`
+    /// The column commitments can not be truncated without a commit key, so it is needed like in `new`
+    ///
+    /// This is sound when every row that is dropped also appears in the first `smaller_n` rows,
+    /// so the restricted table contains exactly the same set of rows. Lookups only care whether a row
+    /// is in the table, not how often it appears. This is always the case when only padding is dropped,
+    /// so restricting a table of `k` rows to any power of two `smaller_n >= k` gives the same table as
+    /// pre-processing it with `smaller_n`
+    /// Returns an error if `smaller_n` is not a power of two, is larger than `n`, or if a row would be lost
+    pub fn restrict<K: CommitKey<PC>>(
+        &self,
+        commit_key: &K,
+        smaller_n: usize,
+    ) -> Result<PreProcessedTable<PC>, TableError> {
+        if !smaller_n.is_power_of_two() || smaller_n > self.n {
+            return Err(TableError::InvalidRestriction {
+                n: self.n,
+                smaller_n,
+            });
+        }
+
+        let (t_1, t_2, t_3) = (&self.t_1.0 .0, &self.t_2.0 .0, &self.t_3.0 .0);
+        let kept: HashSet<_> = (0..smaller_n).map(|i| (t_1[i], t_2[i], t_3[i])).collect();
+        if let Some(index) =
+            (smaller_n..self.n).find(|&i| !kept.contains(&(t_1[i], t_2[i], t_3[i])))
+        {
+            return Err(TableError::RowDropped { index });
+        }
+
+        let t_1 = MultiSet(t_1[..smaller_n].to_vec());
+        let t_2 = MultiSet(t_2[..smaller_n].to_vec());
+        let t_3 = MultiSet(t_3[..smaller_n].to_vec());
+        Ok(PreProcessedTable::with_scheme(
+            commit_key, smaller_n, t_1, t_2, t_3,
+        ))
+    }
+    /// Aggregates the table columns into one multiset using a coefficient for each column
+    /// and sorts the result
+    /// To reuse the result when merging again with the same coefficients, see `MergedTableCache`
+    pub fn merged_table(&self, coefficients: &[Fr; 3]) -> MultiSet {
+        let merged_table =
+            MultiSet::linear_combination3(&self.t_1.0, &self.t_2.0, &self.t_3.0, coefficients);
+        merged_table.sort()
+    }
+    /// Aggregates the distinct rows of the table into one multiset, ordered as required by `sort_order`
+    /// Duplicate rows are removed, keeping the first occurrence, and the table is padded back to size `n` with the last distinct row
+    /// This only changes how often each row occurs, so a witness is a subset of the distinct table exactly when it is a subset of the full table.
+    /// Proofs are always created and verified against every row of the table, so this is only a helper for the multisets
+    pub fn merged_table_distinct(&self, coefficients: &[Fr; 3], sort_order: SortOrder) -> MultiSet {
+        let mut seen = HashSet::new();
+        let mut columns = [MultiSet::new(), MultiSet::new(), MultiSet::new()];
+        let (t_1, t_2, t_3) = (&self.t_1.0, &self.t_2.0, &self.t_3.0);
+        for i in 0..t_1.len() {
+            let row = (t_1.0[i], t_2.0[i], t_3.0[i]);
+            if seen.insert(row) {
+                columns[0].push(row.0);
+                columns[1].push(row.1);
+                columns[2].push(row.2);
+            }
+        }
+
+        let pad_by = self.n - columns[0].len();
+        for column in columns.iter_mut() {
+            column.extend(pad_by, column.last());
+        }
+
+        let merged_table =
+            MultiSet::linear_combination3(&columns[0], &columns[1], &columns[2], coefficients);
+        sort_order.order_table(merged_table)
+    }
+    /// Computes the commitment to the table aggregated with `coefficients` in the order of its rows,
+    /// which is `merged_table_with_order(coefficients, SortOrder::TablePosition)`
+    /// The aggregated table is a linear combination of the columns, so its commitment is the same combination of
+    /// the column commitments, and the verifier can compute it without the commit key
+    pub fn table_commitment(&self, coefficients: &[Fr; 3]) -> PC::Commitment {
+        self.aggregated_commitment(coefficients)
+    }
+    /// Aggregates the table columns into one multiset, ordered as required by `sort_order`
+    /// With `SortOrder::Canonical` this is `merged_table`, otherwise the rows are kept in the order of the columns
+    pub fn merged_table_with_order(
+        &self,
+        coefficients: &[Fr; 3],
+        sort_order: SortOrder,
+    ) -> MultiSet {
+        match sort_order {
+            SortOrder::Canonical => self.merged_table(coefficients),
+            SortOrder::TablePosition => {
+                MultiSet::linear_combination3(&self.t_1.0, &self.t_2.0, &self.t_3.0, coefficients)
+            }
+        }
+    }
+}
+
+/// Selects how the columns of the table and the witness are combined into one multiset
+/// Prover and verifier must use the same strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregationStrategy {
+    /// Columns are combined using powers of one challenge: a + alpha * b + alpha^2 * c
+    Powers,
+    /// Each column after the first has its own challenge: a + alpha_1 * b + alpha_2 * c
+    IndependentChallenges,
+}
+
+impl Default for AggregationStrategy {
+    fn default() -> Self {
+        AggregationStrategy::Powers
+    }
+}
+
+impl AggregationStrategy {
+    /// Computes the coefficient of each of the three columns, deriving any challenges from the transcript
+    pub fn column_coefficients(&self, transcript: &mut dyn TranscriptProtocol) -> [Fr; 3] {
+        let coefficients = self.coefficients(transcript, 3);
+        [coefficients[0], coefficients[1], coefficients[2]]
+    }
+    /// Computes the coefficient of each of `columns` columns, deriving any challenges from the transcript
+    /// The first coefficient is always one, and the challenges are never zero, so neither is any coefficient
+    /// Panics if `columns` is zero, or more than four with `IndependentChallenges`
+    pub fn coefficients(&self, transcript: &mut dyn TranscriptProtocol, columns: usize) -> Vec<Fr> {
+        assert!(columns > 0);
+        match self {
+            AggregationStrategy::Powers => {
+                let alpha = transcript.challenge_nonzero_scalar(labels::ALPHA);
+                transcript.append_scalar(labels::ALPHA, &alpha);
+                powers(alpha, columns)
+            }
+            AggregationStrategy::IndependentChallenges => {
+                let challenge_labels = [labels::ALPHA, labels::ALPHA_2, labels::ALPHA_3];
+                assert!(columns <= challenge_labels.len() + 1);
+
+                let mut coefficients = vec![Fr::one()];
+                for &label in challenge_labels[..columns - 1].iter() {
+                    let alpha = transcript.challenge_nonzero_scalar(label);
+                    transcript.append_scalar(label, &alpha);
+                    coefficients.push(alpha);
+                }
+                coefficients
+            }
+        }
+    }
+}
+
+// Returns [1, x, x^2, ..., x^(len - 1)]
+fn powers(x: Fr, len: usize) -> Vec<Fr> {
+    let mut powers = Vec::with_capacity(len);
+    let mut power = Fr::one();
+    for _ in 0..len {
+        powers.push(power);
+        power *= &x;
+    }
+    powers
+}
+
+/// The columns of a pre-processed table, each padded to a size `n` along with its commitment in the scheme `PC`
+/// A lookup has one witness column for each column of the table it is proven against
+pub trait TableColumns<PC: PolynomialCommitment = KZG10Scheme> {
+    /// Size of the domain that the columns are padded to
+    fn n(&self) -> usize;
+
+    /// Each padded column, along with the commitment to it
+    fn columns(&self) -> Vec<&(MultiSet, PC::Commitment)>;
+
+    /// Aggregates the columns into one multiset using a coefficient for each column,
+    /// ordered as required by `sort_order`
+    fn aggregate_columns(&self, coefficients: &[Fr], sort_order: SortOrder) -> MultiSet {
+        let columns = self
+            .columns()
+            .into_iter()
+            .map(|(column, _)| column)
+            .collect();
+        sort_order.order_table(MultiSet::linear_combination(columns, coefficients))
+    }
+
+    /// Computes the commitment to the columns aggregated with `coefficients` in the order of their rows,
+    /// which is `aggregate_columns(coefficients, SortOrder::TablePosition)`, from the column commitments
+    fn aggregated_commitment(&self, coefficients: &[Fr]) -> PC::Commitment {
+        let commitments: Vec<_> = self
+            .columns()
+            .into_iter()
+            .map(|(_, commitment)| commitment)
+            .collect();
+        PC::linear_combination(&commitments, coefficients)
+    }
+}
+
+impl<PC: PolynomialCommitment> TableColumns<PC> for PreProcessedTable<PC> {
+    fn n(&self) -> usize {
+        self.n
+    }
+
+    fn columns(&self) -> Vec<&(MultiSet, PC::Commitment)> {
+        vec![&self.t_1, &self.t_2, &self.t_3]
+    }
+}
+
+/// Caches the last aggregated table of a pre-processed table, so that aggregating again with the same coefficients,
+/// order and columns skips aggregating and sorting
+/// The coefficients are derived from the transcript, so this only helps when they repeat,
+/// which is when the same transcript is replayed, for example with `LookUp::prove_cached`
+/// The cache is keyed on the columns as well as the coefficients, so it is not stale if the columns are modified.
+/// Comparing the columns is linear in the size of the table, which is cheaper than sorting
+#[derive(Default)]
+pub struct MergedTableCache {
+    last: Mutex<Option<CachedMergedTable>>,
+    hits: AtomicUsize,
+}
+
+// An aggregated table, along with the coefficients, order and columns that it was aggregated from
+struct CachedMergedTable {
+    coefficients: Vec<Fr>,
+    sort_order: SortOrder,
+    columns: Vec<MultiSet>,
+    merged_table: MultiSet,
+}
+
+impl MergedTableCache {
+    pub fn new() -> MergedTableCache {
+        MergedTableCache::default()
+    }
+    /// Returns `preprocessed_table.merged_table(coefficients)`, computing it only if it is not cached
+    pub fn merged_table(
+        &self,
+        preprocessed_table: &PreProcessedTable,
+        coefficients: &[Fr; 3],
+    ) -> MultiSet {
+        self.aggregate_columns(preprocessed_table, coefficients, SortOrder::Canonical)
+    }
+    /// Returns `preprocessed_table.aggregate_columns(coefficients, sort_order)`, computing it only if it is not cached
+    pub fn aggregate_columns<PC: PolynomialCommitment, P: TableColumns<PC>>(
+        &self,
+        preprocessed_table: &P,
+        coefficients: &[Fr],
+        sort_order: SortOrder,
+    ) -> MultiSet {
+        let columns: Vec<&MultiSet> = preprocessed_table
+            .columns()
+            .into_iter()
+            .map(|(column, _)| column)
+            .collect();
+
+        let mut last = self.last.lock().unwrap();
+        if let Some(cached) = &*last {
+            let same_columns = cached.columns.len() == columns.len()
+                && cached
+                    .columns
+                    .iter()
+                    .zip(columns.iter())
+                    .all(|(a, b)| a == *b);
+            if cached.coefficients == coefficients
+                && cached.sort_order == sort_order
+                && same_columns
+            {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return cached.merged_table.clone();
+            }
+        }
+
+        let merged_table = preprocessed_table.aggregate_columns(coefficients, sort_order);
+        *last = Some(CachedMergedTable {
+            coefficients: coefficients.to_vec(),
+            sort_order,
+            columns: columns.into_iter().cloned().collect(),
+            merged_table: merged_table.clone(),
+        });
+        merged_table
+    }
+    /// Returns the number of times an aggregated table was returned from the cache
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+/// Selects how the table is ordered, and how the witness is sorted by the table when computing h_1 and h_2
+/// Prover and verifier must use the same order
+/// The default is `SortOrder::Canonical`. The verifier checks the proof against the table columns with either order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The aggregated table is sorted, and elements are ordered by their canonical integer value
+    /// The order of the aggregated table depends on the aggregation challenges, so the verifier can not
+    /// compute the commitment to it from the column commitments. It aggregates and sorts the columns itself instead,
+    /// which is linear in the size of the table
+    Canonical,
+    /// The aggregated table keeps the order of the rows in the pre-processed columns,
+    /// and elements are ordered by the position at which they first occur in the table
+    /// Pre-processing orders the rows by value, so the table is the same in every process and implementation.
+    /// Every copy of a repeated row is placed at its first position, which is only sorted by the table
+    /// because equal rows are adjacent. Columns which are modified after pre-processing must keep this
+    /// The verifier computes the commitment to the aggregated table from the column commitments
+    TablePosition,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        SortOrder::Canonical
+    }
+}
+
+impl SortOrder {
+    /// Binds the order to the transcript, so that a proof is rejected by a verifier expecting another order
+    /// Nothing is added for `SortOrder::Canonical`, so that those proofs are unchanged
+    pub fn append_to_transcript(&self, transcript: &mut dyn TranscriptProtocol) {
+        if let SortOrder::TablePosition = self {
+            transcript.append_scalar(labels::SORT_ORDER, &Fr::one());
+        }
+    }
+    /// Orders the aggregated table `t`, whose elements are in the order of the table rows
+    pub fn order_table(&self, t: MultiSet) -> MultiSet {
+        match self {
+            SortOrder::Canonical => t.sort(),
+            SortOrder::TablePosition => t,
+        }
+    }
+    /// Sorts `s`, which contains the witness and the table `t`, by the table
+    pub fn sort_by_table(&self, s: &MultiSet, t: &MultiSet) -> MultiSet {
+        match self {
+            SortOrder::Canonical => s.sort(),
+            SortOrder::TablePosition => s.sort_by_position_in(t),
+        }
+    }
+}
+
+/// The result of reading a key from a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadResult {
+    /// The key is in the table, with this value
+    Found(Fr),
+    /// The key is outside of the input domain of the table
+    OutOfDomain,
+    /// The key is in the input domain, but not in the table
+    NotFound,
 }
 
 pub trait LookUpTable {
@@ -31,6 +410,39 @@ pub trait LookUpTable {
         self.borrow_map().get(key)
     }
 
+    /// Returns whether the key is in the input domain of the table
+    /// Tables accept any key by default
+    fn in_domain(&self, _key: &(Fr, Fr)) -> bool {
+        true
+    }
+
+    /// Reads a value from the table, distinguishing keys which are outside of the input domain
+    /// from keys which are in the domain but not in the table
+    fn read_checked(&self, key: &(Fr, Fr)) -> ReadResult {
+        if !self.in_domain(key) {
+            return ReadResult::OutOfDomain;
+        }
+        match self.read(key) {
+            Some(value) => ReadResult::Found(*value),
+            None => ReadResult::NotFound,
+        }
+    }
+
+    /// Checks whether every key can be read from the table
+    /// Returns the keys which are not in the table, in the order they were given
+    fn can_read_all(&self, keys: &[(Fr, Fr)]) -> Result<(), Vec<(Fr, Fr)>> {
+        let missing: Vec<_> = keys
+            .iter()
+            .filter(|key| self.read(key).is_none())
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(missing)
+        }
+    }
+
     /// Given a lookup table where each row contains three entries (a,b,c)
     /// Create three multisets of the form
     /// a = {a_0, a_1, a_2, a_3,...,a_n}
@@ -60,31 +472,8 @@ pub trait LookUpTable {
     /// Pre-process a table by padding it to a size `n` commitment to each column in the table
     /// `n` will usually be equal to the size of your circuit, when padded.
     fn preprocess(&self, commit_key: &Powers<Bls12_381>, n: usize) -> PreProcessedTable {
-        assert!(n.is_power_of_two());
-
-        let (mut t_1, mut t_2, mut t_3) = self.to_multiset();
-
-        let k = t_1.len();
-        assert_eq!(t_1.len(), k);
-        assert_eq!(t_2.len(), k);
-        assert_eq!(t_3.len(), k);
-
-        // Pad
-        let pad_by = n - t_1.len();
-        t_1.extend(pad_by, t_1.last());
-        t_2.extend(pad_by, t_2.last());
-        t_3.extend(pad_by, t_3.last());
-
-        let t_1_commit = kzg10::commit_vec(commit_key, &t_1.0);
-        let t_2_commit = kzg10::commit_vec(commit_key, &t_2.0);
-        let t_3_commit = kzg10::commit_vec(commit_key, &t_3.0);
-
-        PreProcessedTable {
-            n: n,
-            t_1: (t_1, t_1_commit),
-            t_2: (t_2, t_2_commit),
-            t_3: (t_3, t_3_commit),
-        }
+        let (t_1, t_2, t_3) = self.to_multiset();
+        PreProcessedTable::new(commit_key, n, t_1, t_2, t_3)
     }
 }
 
@@ -110,6 +499,313 @@ impl LookUpTable for XOR4BitTable {
     fn borrow_map(&self) -> &HashMap<(Fr, Fr), Fr> {
         &self.0
     }
+
+    // Both inputs must fit into 4 bits
+    fn in_domain(&self, key: &(Fr, Fr)) -> bool {
+        fits_in_bits(&key.0, 4) && fits_in_bits(&key.1, 4)
+    }
+}
+
+/// The default limit on the number of rows in a table built by a table constructor
+pub const DEFAULT_MAX_TABLE_ROWS: usize = 1 << 20;
+
+/// Errors that can occur when building or restricting a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableError {
+    /// The table would have `rows` rows, which is more than the limit `max`
+    TooLarge { rows: u128, max: usize },
+    /// A table of size `n` can only be restricted to a smaller or equal power of two
+    InvalidRestriction { n: usize, smaller_n: usize },
+    /// The row at `index` would be dropped when restricting, and it does not appear in the restricted table
+    RowDropped { index: usize },
+}
+
+/// An XOR table over every pair of `bits`-bit values
+/// The table has 2^(2 * bits) rows
+pub struct XORTable {
+    bits: u32,
+    map: HashMap<(Fr, Fr), Fr>,
+}
+
+impl XORTable {
+    /// Builds the XOR table for `bits`-bit values
+    /// Returns an error instead of allocating, if the table has more than `DEFAULT_MAX_TABLE_ROWS` rows
+    /// Panics if `bits` is zero or more than 32
+    pub fn with_bits(bits: u32) -> Result<XORTable, TableError> {
+        XORTable::with_bits_and_limit(bits, DEFAULT_MAX_TABLE_ROWS)
+    }
+    /// Builds the XOR table for `bits`-bit values, allowing at most `max_table_rows` rows
+    pub fn with_bits_and_limit(bits: u32, max_table_rows: usize) -> Result<XORTable, TableError> {
+        assert!(bits > 0 && bits <= 32);
+
+        let rows = 1u128 << (2 * bits);
+        if rows > max_table_rows as u128 {
+            return Err(TableError::TooLarge {
+                rows,
+                max: max_table_rows,
+            });
+        }
+
+        let max = 1u64 << bits;
+        let mut table = XORTable {
+            bits,
+            map: HashMap::with_capacity(rows as usize),
+        };
+        for i in 0..max {
+            for k in 0..max {
+                table
+                    .map
+                    .insert((Fr::from(i), Fr::from(k)), Fr::from(i ^ k));
+            }
+        }
+        Ok(table)
+    }
+}
+
+impl LookUpTable for XORTable {
+    // Initialise all 4 bit combinations of XOR
+    fn new() -> Self {
+        XORTable::with_bits(4).unwrap()
+    }
+
+    fn borrow_map(&self) -> &HashMap<(Fr, Fr), Fr> {
+        &self.map
+    }
+
+    // Both inputs must fit into `bits` bits
+    fn in_domain(&self, key: &(Fr, Fr)) -> bool {
+        fits_in_bits(&key.0, self.bits) && fits_in_bits(&key.1, self.bits)
+    }
+}
+
+// Returns the order of the rows of the columns, sorting them by the value in each column from first to last
+// Equal rows keep their relative order
+fn row_order(columns: &[&MultiSet]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..columns[0].len()).collect();
+    order.sort_by(|&i, &j| {
+        let row_i = columns.iter().map(|column| column.0[i]);
+        let row_j = columns.iter().map(|column| column.0[j]);
+        row_i.cmp(row_j)
+    });
+    order
+}
+
+// Moves the element at position `order[i]` of the column to position i
+fn permute(column: &MultiSet, order: &[usize]) -> MultiSet {
+    MultiSet(order.iter().map(|&index| column.0[index]).collect())
+}
+
+// Orders the rows of the columns and pads each column to a size `n` by repeating its last row,
+// which is how the columns of a pre-processed table are laid out
+// Panics if `n` is not a power of two, or if the columns do not have the same length
+pub(crate) fn order_and_pad<C: AsMut<[MultiSet]>>(n: usize, mut columns: C) -> C {
+    assert!(n.is_power_of_two());
+
+    let k = columns.as_mut()[0].len();
+    for column in columns.as_mut().iter() {
+        assert_eq!(column.len(), k);
+    }
+
+    let order = {
+        let columns: Vec<&MultiSet> = columns.as_mut().iter().collect();
+        row_order(&columns)
+    };
+    for column in columns.as_mut().iter_mut() {
+        *column = permute(column, &order);
+        let last = column.last();
+        column.extend(n - k, last);
+    }
+    columns
+}
+
+// Returns true if the field element is an integer which can be represented using `bits` bits
+fn fits_in_bits(x: &Fr, bits: u32) -> bool {
+    to_u64(x).map_or(false, |x| bits >= 64 || x < 1u64 << bits)
+}
+
+/// Packs a pair of `bits`-bit values (a, b) into a single field element a * 2^bits + b
+/// The packed key must fit into 64 bits, so `bits` can be at most 32
+/// Panics if either `a` or `b` cannot be represented using `bits` bits
+pub fn pack_key(a: Fr, b: Fr, bits: u32) -> Fr {
+    assert!(bits > 0 && bits <= 32);
+    let max = 1u64 << bits;
+
+    let a = to_u64(&a)
+        .filter(|a| *a < max)
+        .expect("a does not fit into `bits` bits");
+    let b = to_u64(&b)
+        .filter(|b| *b < max)
+        .expect("b does not fit into `bits` bits");
+
+    Fr::from((a << bits) | b)
+}
+
+/// Unpacks a key created with `pack_key` into the pair (a, b)
+/// Panics if `k` cannot be represented using 2 * `bits` bits
+pub fn unpack_key(k: Fr, bits: u32) -> (Fr, Fr) {
+    assert!(bits > 0 && bits <= 32);
+    let mask = (1u64 << bits) - 1;
+
+    let k = to_u64(&k)
+        .filter(|k| (*k >> bits) <= mask)
+        .expect("k does not fit into `2 * bits` bits");
+
+    (Fr::from(k >> bits), Fr::from(k & mask))
+}
+
+/// A 4 bit XOR table, where both inputs have been packed into the first key
+/// using `pack_key`. The second key is always zero, so the table effectively has two columns
+/// Reads should be made with the key (pack_key(a, b, 4), 0)
+pub struct PackedXOR4BitTable(HashMap<(Fr, Fr), Fr>);
+
+impl LookUpTable for PackedXOR4BitTable {
+    // Initialise all 4 bit combinations of XOR, keyed on the packed inputs
+    fn new() -> Self {
+        let mut table = PackedXOR4BitTable(HashMap::new());
+
+        for i in 0..=15u8 {
+            for k in 0..=15u8 {
+                let result = i ^ k;
+                let key = pack_key(Fr::from(i), Fr::from(k), 4);
+                table.0.insert((key, Fr::zero()), Fr::from(result));
+            }
+        }
+        table
+    }
+
+    fn borrow_map(&self) -> &HashMap<(Fr, Fr), Fr> {
+        &self.0
+    }
+
+    // The packed key must fit into 8 bits, and the second key must be zero
+    fn in_domain(&self, key: &(Fr, Fr)) -> bool {
+        fits_in_bits(&key.0, 8) && key.1.is_zero()
+    }
+}
+
+/// A table where each key maps to two outputs, for example the sum and carry of an adder
+/// Each row (a, b, c, d) of the table is made up of the key (a, b) and the outputs (c, d)
+pub trait TwoOutputLookUpTable {
+    /// Creates a new lookup table with its entries populated
+    fn new() -> Self;
+
+    /// Returns the number of entries in the lookup table
+    fn len(&self) -> usize {
+        self.borrow_map().keys().len()
+    }
+
+    /// We represent the lookup table as a map
+    /// Returns an immutable copy of the map
+    fn borrow_map(&self) -> &HashMap<(Fr, Fr), (Fr, Fr)>;
+
+    /// Fetches both outputs of the lookup table, given its input
+    fn read(&self, key: &(Fr, Fr)) -> Option<&(Fr, Fr)> {
+        self.borrow_map().get(key)
+    }
+
+    /// Creates one multiset for each of the four columns of the table
+    fn to_multiset(&self) -> [MultiSet; 4] {
+        let mut columns = [
+            MultiSet::new(),
+            MultiSet::new(),
+            MultiSet::new(),
+            MultiSet::new(),
+        ];
+
+        for (key, value) in self.borrow_map().iter() {
+            columns[0].push(key.0);
+            columns[1].push(key.1);
+            columns[2].push(value.0);
+            columns[3].push(value.1);
+        }
+
+        columns
+    }
+    /// Pre-process a table by padding it to a size `n` commitment to each column in the table
+    fn preprocess(&self, commit_key: &Powers<Bls12_381>, n: usize) -> PreProcessedTwoOutputTable {
+        PreProcessedTwoOutputTable::new(commit_key, n, self.to_multiset())
+    }
+}
+
+/// The columns of a table with two outputs, padded to a size `n`, along with the commitment to each column
+/// The rows are ordered by their values, like in `PreProcessedTable`
+pub struct PreProcessedTwoOutputTable {
+    pub n: usize,
+    pub columns: [(MultiSet, Commitment<Bls12_381>); 4],
+}
+
+impl PreProcessedTwoOutputTable {
+    /// Pre-process the columns of a table by padding them to a size `n` and committing to each column
+    pub fn new(
+        commit_key: &Powers<Bls12_381>,
+        n: usize,
+        columns: [MultiSet; 4],
+    ) -> PreProcessedTwoOutputTable {
+        // Order the rows, like `PreProcessedTable::new`
+        let [t_1, t_2, t_3, t_4] = order_and_pad(n, columns);
+
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(n).unwrap();
+        let pad = |column: MultiSet| {
+            let commitment = kzg10::commit(commit_key, &column.to_polynomial(&domain));
+            (column, commitment)
+        };
+
+        PreProcessedTwoOutputTable {
+            n,
+            columns: [pad(t_1), pad(t_2), pad(t_3), pad(t_4)],
+        }
+    }
+}
+
+impl TableColumns for PreProcessedTwoOutputTable {
+    fn n(&self) -> usize {
+        self.n
+    }
+
+    fn columns(&self) -> Vec<&(MultiSet, Commitment<Bls12_381>)> {
+        self.columns.iter().collect()
+    }
+}
+
+/// Adds two 4 bit values, the outputs are the 4 bit sum and the carry
+/// Each row is (a, b, (a + b) mod 16, (a + b) / 16)
+pub struct Add4BitTable(HashMap<(Fr, Fr), (Fr, Fr)>);
+
+impl TwoOutputLookUpTable for Add4BitTable {
+    // Initialise all 4 bit combinations of addition
+    fn new() -> Self {
+        let mut table = Add4BitTable(HashMap::new());
+
+        for i in 0..=15u8 {
+            for k in 0..=15u8 {
+                let result = i + k;
+                table.0.insert(
+                    (Fr::from(i), Fr::from(k)),
+                    (Fr::from(result % 16), Fr::from(result / 16)),
+                );
+            }
+        }
+        table
+    }
+
+    fn borrow_map(&self) -> &HashMap<(Fr, Fr), (Fr, Fr)> {
+        &self.0
+    }
+}
+
+#[test]
+fn test_pack_unpack_key() {
+    for i in 0..=15u8 {
+        for k in 0..=15u8 {
+            let packed = pack_key(Fr::from(i), Fr::from(k), 4);
+            assert_eq!(packed, Fr::from(i as u64 * 16 + k as u64));
+            assert_eq!(unpack_key(packed, 4), (Fr::from(i), Fr::from(k)));
+        }
+    }
+
+    let a = Fr::from(u32::MAX as u64);
+    let b = Fr::from(7u64);
+    assert_eq!(unpack_key(pack_key(a, b, 32), 32), (a, b));
 }
 
 #[test]
@@ -117,3 +813,318 @@ fn test_size_bit_table() {
     let four_bit_table = XOR4BitTable::new();
     assert_eq!(four_bit_table.0.len(), 2usize.pow(8))
 }
+
+#[test]
+fn test_size_packed_bit_table() {
+    let four_bit_table = PackedXOR4BitTable::new();
+    assert_eq!(four_bit_table.0.len(), 2usize.pow(8))
+}
+
+#[test]
+fn test_can_read_all() {
+    let table = XOR4BitTable::new();
+
+    let valid = vec![
+        (Fr::from(1u8), Fr::from(2u8)),
+        (Fr::from(15u8), Fr::from(15u8)),
+    ];
+    assert_eq!(table.can_read_all(&valid), Ok(()));
+    assert_eq!(table.can_read_all(&[]), Ok(()));
+
+    let invalid = vec![
+        (Fr::from(1u8), Fr::from(2u8)),
+        (Fr::from(16u8), Fr::from(0u8)),
+        (Fr::from(3u8), Fr::from(4u8)),
+        (Fr::from(0u8), Fr::from(100u8)),
+    ];
+    assert_eq!(
+        table.can_read_all(&invalid),
+        Err(vec![
+            (Fr::from(16u8), Fr::from(0u8)),
+            (Fr::from(0u8), Fr::from(100u8))
+        ])
+    );
+}
+
+#[test]
+fn test_merged_table_cache() {
+    let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+    let mut preprocessed_table = XOR4BitTable::new().preprocess(&proving_key, 2usize.pow(8));
+    let cache = MergedTableCache::new();
+
+    let alpha = Fr::from(7u64);
+    let coefficients = [Fr::one(), alpha, alpha * alpha];
+
+    let uncached = preprocessed_table.merged_table(&coefficients);
+    // The first call fills the cache and the second call reads from it
+    assert_eq!(
+        cache.merged_table(&preprocessed_table, &coefficients),
+        uncached
+    );
+    assert_eq!(cache.hits(), 0);
+    assert_eq!(
+        cache.merged_table(&preprocessed_table, &coefficients),
+        uncached
+    );
+    assert_eq!(cache.hits(), 1);
+
+    // The same coefficients in another order are aggregated again
+    assert_eq!(
+        cache.aggregate_columns(&preprocessed_table, &coefficients, SortOrder::TablePosition),
+        preprocessed_table.merged_table_with_order(&coefficients, SortOrder::TablePosition)
+    );
+    assert_eq!(cache.hits(), 1);
+
+    // Different coefficients replace the cached table
+    let beta = Fr::from(11u64);
+    let other_coefficients = [Fr::one(), beta, beta * beta];
+    assert_eq!(
+        cache.merged_table(&preprocessed_table, &other_coefficients),
+        preprocessed_table.merged_table(&other_coefficients)
+    );
+    assert_ne!(
+        cache.merged_table(&preprocessed_table, &other_coefficients),
+        uncached
+    );
+
+    // Modifying the columns does not return the stale table
+    preprocessed_table.t_3.0 .0[0] = Fr::from(100u64);
+    let modified = preprocessed_table.merged_table(&other_coefficients);
+    assert_eq!(
+        cache.merged_table(&preprocessed_table, &other_coefficients),
+        modified
+    );
+
+    // The table and the cache can be shared between threads
+    fn assert_sync<T: Sync>() {}
+    assert_sync::<PreProcessedTable>();
+    assert_sync::<MergedTableCache>();
+}
+
+#[test]
+fn test_xor_table_too_large() {
+    // A 16 bit XOR table would have 2^32 rows
+    assert_eq!(
+        XORTable::with_bits(16).err(),
+        Some(TableError::TooLarge {
+            rows: 1 << 32,
+            max: DEFAULT_MAX_TABLE_ROWS
+        })
+    );
+    assert_eq!(
+        XORTable::with_bits_and_limit(4, 255).err(),
+        Some(TableError::TooLarge {
+            rows: 256,
+            max: 255
+        })
+    );
+
+    // The limit can be raised explicitly
+    let table = XORTable::with_bits_and_limit(5, 1 << 10).unwrap();
+    assert_eq!(table.len(), 1 << 10);
+
+    let table = XORTable::new();
+    assert_eq!(table.borrow_map(), XOR4BitTable::new().borrow_map());
+}
+
+#[test]
+fn test_merged_table_distinct() {
+    let (proving_key, _) = kzg10::trusted_setup(2usize.pow(4), b"insecure_seed");
+
+    // The rows (1, 2, 3) and (4, 5, 6) are repeated
+    let t_1 = MultiSet::from_u64s(&[1, 4, 1, 7, 4]);
+    let t_2 = MultiSet::from_u64s(&[2, 5, 2, 8, 5]);
+    let t_3 = MultiSet::from_u64s(&[3, 6, 3, 9, 6]);
+    let preprocessed_table = PreProcessedTable::new(&proving_key, 8, t_1, t_2, t_3);
+
+    let alpha = Fr::from(10u64);
+    let coefficients = [Fr::one(), alpha, alpha * alpha];
+
+    // The rows are ordered when pre-processing, so both tables are padded with the largest row (7, 8, 9)
+    let merged = preprocessed_table.merged_table(&coefficients);
+    let distinct = preprocessed_table.merged_table_distinct(&coefficients, SortOrder::Canonical);
+    assert_eq!(
+        merged,
+        MultiSet::from_u64s(&[321, 321, 654, 654, 987, 987, 987, 987])
+    );
+    assert_eq!(
+        distinct,
+        MultiSet::from_u64s(&[321, 654, 987, 987, 987, 987, 987, 987])
+    );
+
+    // Both tables contain the same rows, only the number of times each row occurs differs
+    assert!(merged.0.iter().all(|x| distinct.contains(x)));
+    assert!(distinct.0.iter().all(|x| merged.contains(x)));
+}
+
+#[test]
+fn test_read_checked() {
+    let table = XOR4BitTable::new();
+    assert_eq!(
+        table.read_checked(&(Fr::from(8u8), Fr::from(10u8))),
+        ReadResult::Found(Fr::from(2u8))
+    );
+    assert_eq!(
+        table.read_checked(&(Fr::from(16u8), Fr::from(6u8))),
+        ReadResult::OutOfDomain
+    );
+    assert_eq!(
+        table.read_checked(&(Fr::from(6u8), -Fr::one())),
+        ReadResult::OutOfDomain
+    );
+
+    // A key in the domain, which has been removed from the table
+    let mut table = XOR4BitTable::new();
+    table.0.remove(&(Fr::from(8u8), Fr::from(10u8)));
+    assert_eq!(
+        table.read_checked(&(Fr::from(8u8), Fr::from(10u8))),
+        ReadResult::NotFound
+    );
+
+    let table = PackedXOR4BitTable::new();
+    assert_eq!(
+        table.read_checked(&(pack_key(Fr::from(8u8), Fr::from(10u8), 4), Fr::zero())),
+        ReadResult::Found(Fr::from(2u8))
+    );
+    assert_eq!(
+        table.read_checked(&(Fr::from(256u64), Fr::zero())),
+        ReadResult::OutOfDomain
+    );
+    assert_eq!(
+        table.read_checked(&(Fr::from(1u8), Fr::one())),
+        ReadResult::OutOfDomain
+    );
+}
+#[test]
+fn test_add_4bit_table() {
+    let table = Add4BitTable::new();
+    assert_eq!(table.len(), 256);
+    assert_eq!(
+        table.read(&(Fr::from(9u8), Fr::from(8u8))),
+        Some(&(Fr::from(1u8), Fr::from(1u8)))
+    );
+    assert_eq!(
+        table.read(&(Fr::from(3u8), Fr::from(4u8))),
+        Some(&(Fr::from(7u8), Fr::zero()))
+    );
+    assert_eq!(table.read(&(Fr::from(16u8), Fr::zero())), None);
+
+    let columns = table.to_multiset();
+    for column in columns.iter() {
+        assert_eq!(column.len(), 256);
+    }
+}
+#[test]
+fn test_restrict() {
+    use crate::lookup::LookUp;
+    use crate::proof::MultiSetEqualityProof;
+    use merlin::Transcript;
+
+    let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+    let table = XOR4BitTable::new();
+    let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(12));
+
+    // Only padding is dropped, so this is the same as pre-processing with the smaller size
+    let restricted = preprocessed_table
+        .restrict(&proving_key, 2usize.pow(8))
+        .unwrap();
+    let expected = table.preprocess(&proving_key, 2usize.pow(8));
+    assert_eq!(restricted.n, 2usize.pow(8));
+    assert_eq!(restricted.t_1, expected.t_1);
+    assert_eq!(restricted.t_2, expected.t_2);
+    assert_eq!(restricted.t_3, expected.t_3);
+
+    let mut lookup = LookUp::new(table);
+    lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+    lookup.read(&(Fr::from(15u8), Fr::from(3u8)));
+
+    let mut prover_transcript = Transcript::new(b"lookup");
+    let proof: MultiSetEqualityProof = lookup
+        .prove(&proving_key, &restricted, &mut prover_transcript)
+        .unwrap();
+
+    let mut verifier_transcript = Transcript::new(b"lookup");
+    let ok = proof
+        .verify(&verifier_key, &restricted, &mut verifier_transcript)
+        .is_ok();
+    assert!(ok);
+
+    // Restricting further would drop rows of the table
+    assert_eq!(
+        restricted.restrict(&proving_key, 2usize.pow(7)).err(),
+        Some(TableError::RowDropped { index: 128 })
+    );
+    assert_eq!(
+        restricted.restrict(&proving_key, 2usize.pow(9)).err(),
+        Some(TableError::InvalidRestriction {
+            n: 2usize.pow(8),
+            smaller_n: 2usize.pow(9)
+        })
+    );
+    assert_eq!(
+        restricted.restrict(&proving_key, 100).err(),
+        Some(TableError::InvalidRestriction {
+            n: 2usize.pow(8),
+            smaller_n: 100
+        })
+    );
+}
+#[test]
+fn test_row_order() {
+    use crate::lookup::LookUp;
+    use crate::proof::MultiSetEqualityProof;
+    use merlin::Transcript;
+
+    let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+    let table = XOR4BitTable::new();
+
+    // The same rows given in a different order are pre-processed to the same table
+    let (t_1, t_2, t_3) = table.to_multiset();
+    let preprocessed_table = PreProcessedTable::new(
+        &proving_key,
+        2usize.pow(8),
+        t_1.clone(),
+        t_2.clone(),
+        t_3.clone(),
+    );
+    let reordered_table = PreProcessedTable::new(
+        &proving_key,
+        2usize.pow(8),
+        t_1.reversed(),
+        t_2.reversed(),
+        t_3.reversed(),
+    );
+    assert_eq!(preprocessed_table.t_1, reordered_table.t_1);
+    assert_eq!(preprocessed_table.t_2, reordered_table.t_2);
+    assert_eq!(preprocessed_table.t_3, reordered_table.t_3);
+    assert_eq!(preprocessed_table.t_1.0, preprocessed_table.t_1.0.sort());
+
+    // A row which is repeated away from its first occurrence becomes adjacent to it,
+    // so a lookup of that row can be proven when the table is ordered by position
+    let repeated = (Fr::from(3u8), Fr::from(5u8), Fr::from(6u8));
+    let mut columns = [t_1, t_2, t_3];
+    columns[0].push(repeated.0);
+    columns[1].push(repeated.1);
+    columns[2].push(repeated.2);
+    let [t_1, t_2, t_3] = columns;
+    let preprocessed_table = PreProcessedTable::new(&proving_key, 2usize.pow(9), t_1, t_2, t_3);
+
+    let mut lookup = LookUp::new(table).with_sort_order(SortOrder::TablePosition);
+    lookup.read(&(repeated.0, repeated.1));
+
+    let mut prover_transcript = Transcript::new(b"lookup");
+    let proof: MultiSetEqualityProof = lookup
+        .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+        .unwrap();
+
+    let mut verifier_transcript = Transcript::new(b"lookup");
+    let ok = proof
+        .verify_with_sort_order(
+            &verifier_key,
+            &preprocessed_table,
+            SortOrder::TablePosition,
+            &mut verifier_transcript,
+        )
+        .is_ok();
+    assert!(ok);
+}
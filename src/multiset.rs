@@ -1,7 +1,31 @@
-use algebra::bls12_381::Fr;
+use crate::parallel;
+use algebra::bls12_381::{Fr, FrParameters};
+#[cfg(any(test, feature = "random"))]
+use algebra::UniformRand;
+use algebra_core::biginteger::BigInteger256;
+use algebra_core::fields::{FpParameters, PrimeField};
 use ff_fft::{DensePolynomial as Polynomial, EvaluationDomain};
 use num_traits::identities::{One, Zero};
+#[cfg(any(test, feature = "random"))]
+use rand::Rng;
+use std::collections::HashMap;
 use std::ops::{Add, Mul};
+
+/// Errors that can occur when operating on a multiset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultiSetError {
+    /// The operation requires at least one element
+    Empty,
+    /// The element at this index does not fit into a u64
+    Overflow { index: usize },
+    /// The element occurs more often in the set being subtracted
+    Underflow(Fr),
+    /// The element at this index is not reduced modulo the field order
+    NonCanonical { index: usize },
+    /// The operation is degenerate for a set with a single element
+    SingleElement,
+}
+
 /// A MultiSet is a variation of a set, where we allow duplicate members
 /// This can be emulated in Rust by using vectors
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -12,10 +36,58 @@ impl MultiSet {
     pub fn new() -> MultiSet {
         MultiSet(vec![])
     }
+    /// Creates a multiset from a slice of integers
+    pub fn from_u64s(values: &[u64]) -> MultiSet {
+        MultiSet(values.iter().map(|value| Fr::from(*value)).collect())
+    }
+    /// Splits a list of rows into its three columns
+    /// The i'th element of each column comes from the i'th row
+    pub fn from_rows(rows: &[(Fr, Fr, Fr)]) -> (MultiSet, MultiSet, MultiSet) {
+        let mut a = MultiSet(Vec::with_capacity(rows.len()));
+        let mut b = MultiSet(Vec::with_capacity(rows.len()));
+        let mut c = MultiSet(Vec::with_capacity(rows.len()));
+        for (x, y, z) in rows.iter() {
+            a.push(*x);
+            b.push(*y);
+            c.push(*z);
+        }
+        (a, b, c)
+    }
+    /// Converts each element in the multiset to an integer
+    /// Returns an error with the index of the first element which exceeds u64::MAX
+    pub fn to_u64s(&self) -> Result<Vec<u64>, MultiSetError> {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(index, element)| to_u64(element).ok_or(MultiSetError::Overflow { index }))
+            .collect()
+    }
+    /// Converts each element in the multiset to its integer representation
+    pub fn to_bigints(&self) -> Vec<BigInteger256> {
+        self.0.iter().map(|element| element.into_repr()).collect()
+    }
+    /// Maps every element to its canonical representation
+    /// Field arithmetic always returns reduced elements, so this is only needed when elements
+    /// were created from raw limbs, for example with `Fr::new`, which are not checked to be reduced.
+    /// A non-reduced element compares, hashes and serialises differently to its reduced form
+    pub fn canonicalize(&mut self) {
+        for element in self.0.iter_mut() {
+            *element = Fr::from_repr(element.into_repr());
+        }
+    }
     /// Pushes a value onto the end of the set
     pub fn push(&mut self, value: Fr) {
         self.0.push(value)
     }
+    /// Appends every value to the set if all of them are canonical
+    /// Otherwise returns an error with the index of the first non-canonical value and leaves the set unchanged
+    pub fn try_extend_canonical(&mut self, values: &[Fr]) -> Result<(), MultiSetError> {
+        if let Some(index) = values.iter().position(|value| !is_canonical(value)) {
+            return Err(MultiSetError::NonCanonical { index });
+        }
+        self.0.extend_from_slice(values);
+        Ok(())
+    }
     /// Pushes 'n' elements into the multiset
     pub fn extend(&mut self, n: usize, value: Fr) {
         let elements = vec![value; n];
@@ -26,20 +98,90 @@ impl MultiSet {
     pub fn last(&self) -> Fr {
         *self.0.last().unwrap()
     }
+    /// Returns the i'th element, or None if `i` is out of bounds
+    /// When the multiset is in evaluation form over a domain, as in `to_polynomial`,
+    /// this is the evaluation at the i'th domain element w^i, not the i'th coefficient
+    pub fn eval_at_index(&self, i: usize) -> Option<Fr> {
+        self.0.get(i).copied()
+    }
+    /// Fetches the first and last element in the multiset
+    /// For a multiset with one element, both are the same element
+    pub fn boundary(&self) -> Result<(Fr, Fr), MultiSetError> {
+        match (self.0.first(), self.0.last()) {
+            (Some(first), Some(last)) => Ok((*first, *last)),
+            _ => Err(MultiSetError::Empty),
+        }
+    }
     fn from_slice(slice: &[Fr]) -> MultiSet {
         MultiSet(slice.to_vec())
     }
+    /// Creates a multiset from an iterator, reserving space for `cap` elements up front
+    /// Useful when the number of elements is known, but the iterator's size hint is not reliable
+    pub fn from_iter_with_capacity(iter: impl Iterator<Item = Fr>, cap: usize) -> MultiSet {
+        let mut elements = Vec::with_capacity(cap);
+        elements.extend(iter);
+        MultiSet(elements)
+    }
     /// Returns the cardinality of the multiset
     pub fn len(&self) -> usize {
         self.0.len()
     }
+    /// Returns the elements of the multiset as a slice
+    pub fn as_slice(&self) -> &[Fr] {
+        &self.0
+    }
 
     /// Sorts an multiset in ascending order
+    /// Large multisets are sorted in parallel, see `parallel::parallel_threshold`
     pub fn sort(&self) -> MultiSet {
         let mut cloned = self.0.clone();
-        cloned.sort();
+        parallel::sort(&mut cloned);
         MultiSet(cloned)
     }
+    /// Sorts the multiset in ascending order like `sort`, and returns the permutation that was applied
+    /// The element at position i of self is moved to position `perm[i]` of the sorted multiset
+    /// Equal elements keep their relative order
+    pub fn sort_with_perm(&self) -> (MultiSet, Vec<usize>) {
+        let mut order: Vec<usize> = (0..self.len()).collect();
+        order.sort_by(|&i, &j| self.0[i].cmp(&self.0[j]));
+
+        let mut perm = vec![0; self.len()];
+        for (sorted_index, &index) in order.iter().enumerate() {
+            perm[index] = sorted_index;
+        }
+        let sorted = MultiSet(order.iter().map(|&index| self.0[index]).collect());
+
+        (sorted, perm)
+    }
+    /// Sorts the multiset by the position at which each element first occurs in `t`
+    /// Elements which are not in `t` are moved to the end, in ascending order
+    /// Every copy of an element is placed at its first position, so the result is only sorted by `t`
+    /// when equal elements of `t` are adjacent
+    pub fn sort_by_position_in(&self, t: &MultiSet) -> MultiSet {
+        let mut positions: HashMap<Fr, usize> = HashMap::with_capacity(t.len());
+        for (position, element) in t.0.iter().enumerate() {
+            positions.entry(*element).or_insert(position);
+        }
+
+        let mut sorted = self.0.clone();
+        sorted.sort_by_key(|element| {
+            (
+                positions.get(element).copied().unwrap_or(usize::MAX),
+                *element,
+            )
+        });
+        MultiSet(sorted)
+    }
+    /// Reverses the order of the elements in the multiset, in place
+    pub fn reverse(&mut self) {
+        self.0.reverse()
+    }
+    /// Returns a copy of the multiset with the order of the elements reversed
+    pub fn reversed(&self) -> MultiSet {
+        let mut cloned = self.clone();
+        cloned.reverse();
+        cloned
+    }
     /// Concatenates two sets together
     /// Does not sort the concatenated multisets
     pub fn concatenate(&self, other: &MultiSet) -> MultiSet {
@@ -48,6 +190,26 @@ impl MultiSet {
         result.extend(&other.0);
         MultiSet(result)
     }
+    /// Moves every element of `other` onto the end of self, leaving `other` empty
+    pub fn append(&mut self, other: &mut MultiSet) {
+        self.0.append(&mut other.0)
+    }
+    /// Moves every element of `other` onto the end of self
+    /// Unlike `concatenate`, this does not copy the elements of either set
+    pub fn extend_owned(&mut self, mut other: MultiSet) {
+        self.append(&mut other)
+    }
+    /// Concatenates two sets together, shifting every element of `other` by `tag`
+    /// This separates the values of `other` from the values of `self`, so that a value which
+    /// appears in both sets can be told apart after concatenation.
+    /// The tag should either be a random challenge, or larger than every value in `self`,
+    /// otherwise a shifted value of `other` may collide with a value in `self`
+    pub fn concat_with_tag(&self, other: &MultiSet, tag: Fr) -> MultiSet {
+        let mut result: Vec<Fr> = Vec::with_capacity(self.0.len() + other.0.len());
+        result.extend(&self.0);
+        result.extend(other.0.iter().map(|x| *x + tag));
+        MultiSet(result)
+    }
     /// SortedBy checks whether every value in self appears in the same order as t
     /// Example: self = [1,2,2] t = [1,2,3] returns true
     /// Example : self = [2,1] t = [1,2] returns false
@@ -59,23 +221,76 @@ impl MultiSet {
         }
         true
     }
+    /// Returns each value in the multiset along with the number of times it is repeated consecutively
+    /// If the multiset is sorted, this gives every distinct value and its multiplicity
+    /// Example: self = [1,1,2,3,3,3] returns [(1,2), (2,1), (3,3)]
+    pub fn run_length(&self) -> Vec<(Fr, usize)> {
+        let mut runs: Vec<(Fr, usize)> = Vec::new();
+        for element in self.0.iter() {
+            if let Some((value, count)) = runs.last_mut() {
+                if *value == *element {
+                    *count += 1;
+                    continue;
+                }
+            }
+            runs.push((*element, 1));
+        }
+        runs
+    }
     /// Checks whether self is a subset of other
+    /// Each element must occur in `other` at least as often as it occurs in self
     pub fn is_subset_of(&self, other: &MultiSet) -> bool {
-        assert!(other.len() >= self.len());
-
-        let mut is_subset = true;
+        let (_, remainder) = other.difference_with_remainder(self);
+        remainder.is_empty()
+    }
+    /// Checks if an element is in the MultiSet
+    pub fn contains(&self, element: &Fr) -> bool {
+        self.0.contains(element)
+    }
+    /// Computes the multiset difference `self \ other`
+    /// Each element of `other` removes one occurrence of it from `self`
+    /// Elements which occur more often in `other` than in `self` are clamped at zero
+    pub fn difference(&self, other: &MultiSet) -> MultiSet {
+        self.difference_with_remainder(other).0
+    }
+    /// Computes the multiset difference `self \ other`
+    /// Returns an error with the first element of `other` that occurs more often in `other` than in `self`
+    pub fn try_difference(&self, other: &MultiSet) -> Result<MultiSet, MultiSetError> {
+        let (difference, remainder) = self.difference_with_remainder(other);
+        match other.0.iter().find(|x| remainder.contains_key(*x)) {
+            Some(x) => Err(MultiSetError::Underflow(*x)),
+            None => Ok(difference),
+        }
+    }
+    // Returns the difference, along with the elements of `other` which were not removed from `self`
+    fn difference_with_remainder(&self, other: &MultiSet) -> (MultiSet, HashMap<Fr, usize>) {
+        let mut remainder: HashMap<Fr, usize> = HashMap::new();
+        for x in other.0.iter() {
+            *remainder.entry(*x).or_insert(0) += 1;
+        }
 
+        let mut difference = MultiSet::new();
         for x in self.0.iter() {
-            is_subset = other.contains(x);
-            if is_subset == false {
-                break;
+            match remainder.get_mut(x) {
+                Some(count) => {
+                    *count -= 1;
+                    if *count == 0 {
+                        remainder.remove(x);
+                    }
+                }
+                None => difference.push(*x),
             }
         }
-        is_subset
+
+        (difference, remainder)
     }
-    /// Checks if an element is in the MultiSet
-    pub fn contains(&self, element: &Fr) -> bool {
-        self.0.contains(element)
+    /// Folds every element in the multiset into an accumulator, starting from `init`
+    pub fn fold<B, F: FnMut(B, &Fr) -> B>(&self, init: B, f: F) -> B {
+        self.0.iter().fold(init, f)
+    }
+    /// Calls `f` on every element in the multiset, stopping at the first error, which is returned
+    pub fn try_for_each<E>(&self, f: impl FnMut(&Fr) -> Result<(), E>) -> Result<(), E> {
+        self.0.iter().try_for_each(f)
     }
     /// Splits a multiset into halves as specified by the paper
     /// If s = [1,2,3,4,5,6,7], we can deduce n using |s| = 2 * n + 1 = 7
@@ -85,13 +300,20 @@ impl MultiSet {
     /// s_1 = [4,5,6,7] , |s_1| = n+1 = 4
     /// Notice that the last element of the first half equals the first element in the second half
     /// This is specified in the paper
-    pub fn halve(&self) -> (MultiSet, MultiSet) {
+    /// A single element set gives n = 0, so both halves would be the same element and the relation is degenerate.
+    /// Returns an error for sets with fewer than two elements
+    pub fn halve(&self) -> Result<(MultiSet, MultiSet), MultiSetError> {
         let length = self.0.len();
+        match length {
+            0 => return Err(MultiSetError::Empty),
+            1 => return Err(MultiSetError::SingleElement),
+            _ => {}
+        }
 
         let first_half = MultiSet::from_slice(&self.0[0..=length / 2]);
         let second_half = MultiSet::from_slice(&self.0[length / 2..]);
 
-        (first_half, second_half)
+        Ok((first_half, second_half))
     }
     /// Treats each element in the multiset as evaluation points
     /// Computes IFFT of the set of evaluation points
@@ -99,9 +321,27 @@ impl MultiSet {
     pub fn to_polynomial(&self, domain: &EvaluationDomain<Fr>) -> Polynomial<Fr> {
         Polynomial::from_coefficients_vec(domain.ifft(&self.0))
     }
+    /// Combines multisets together using one coefficient per set
+    /// Eg. for three sets A,B,C and coefficients a,b,c
+    /// The combination is a * A + b * B + c * C
+    /// Large multisets are combined in parallel, see `parallel::aggregate_threshold`
+    pub fn linear_combination(sets: Vec<&MultiSet>, coefficients: &[Fr]) -> MultiSet {
+        assert_eq!(sets.len(), coefficients.len());
+
+        // First find the set with the most elements
+        let max = sets.iter().map(|set| set.len()).max().unwrap_or(0);
+
+        let mut result = vec![Fr::zero(); max];
+        for (set, coefficient) in sets.into_iter().zip(coefficients) {
+            parallel::add_scaled(&mut result, &set.0, *coefficient);
+        }
+
+        MultiSet(result)
+    }
     /// Aggregates multisets together using a random challenge
     /// Eg. for three sets A,B,C and a random challenge `k`
     /// The aggregate is k^0 *A + k^1 * B + k^2 * C
+    /// Large multisets are aggregated in parallel, see `parallel::aggregate_threshold`
     pub fn aggregate(sets: Vec<&MultiSet>, challenge: Fr) -> MultiSet {
         // First find the set with the most elements
         let mut max = 0usize;
@@ -111,19 +351,73 @@ impl MultiSet {
             }
         }
 
-        let mut result = MultiSet(vec![Fr::zero(); max]);
+        let mut result = vec![Fr::zero(); max];
         let mut powers = Fr::one();
 
         for set in sets {
-            let intermediate_set = set * powers;
-
-            result = result + intermediate_set;
+            parallel::add_scaled(&mut result, &set.0, powers);
 
             powers = powers * challenge;
         }
 
-        result
+        MultiSet(result)
+    }
+    /// Aggregates three multisets of the same length using a random challenge `k`
+    /// The aggregate is k^0 * A + k^1 * B + k^2 * C, as computed by `aggregate`
+    /// Panics if the sets have different lengths
+    pub fn aggregate3(a: &MultiSet, b: &MultiSet, c: &MultiSet, challenge: Fr) -> MultiSet {
+        MultiSet::linear_combination3(a, b, c, &[Fr::one(), challenge, challenge * challenge])
+    }
+    /// Combines three multisets of the same length using one coefficient per set,
+    /// as computed by `linear_combination`
+    /// Panics if the sets have different lengths
+    pub fn linear_combination3(
+        a: &MultiSet,
+        b: &MultiSet,
+        c: &MultiSet,
+        coefficients: &[Fr; 3],
+    ) -> MultiSet {
+        assert_eq!(a.len(), b.len());
+        assert_eq!(a.len(), c.len());
+
+        let [k_a, k_b, k_c] = *coefficients;
+        MultiSet(
+            a.0.iter()
+                .zip(b.0.iter())
+                .zip(c.0.iter())
+                .map(|((x, y), z)| k_a * x + &(k_b * y) + &(k_c * z))
+                .collect(),
+        )
+    }
+    /// Creates a MultiSet of `len` uniformly random field elements
+    #[cfg(any(test, feature = "random"))]
+    pub fn random<R: Rng>(len: usize, rng: &mut R) -> MultiSet {
+        MultiSet((0..len).map(|_| Fr::rand(rng)).collect())
     }
+    /// Samples `len` elements from `table` without replacement,
+    /// so that the result is always a subset of `table`
+    /// Panics if `len` is larger than the table
+    #[cfg(any(test, feature = "random"))]
+    pub fn random_subset_of<R: Rng>(table: &MultiSet, len: usize, rng: &mut R) -> MultiSet {
+        assert!(len <= table.len());
+        let indices = rand::seq::index::sample(rng, table.len(), len);
+        MultiSet(indices.into_iter().map(|i| table.0[i]).collect())
+    }
+}
+
+// Checks that the internal representation of a field element is reduced modulo the field order
+fn is_canonical(x: &Fr) -> bool {
+    x.0 < FrParameters::MODULUS
+}
+
+// Returns the integer value of a field element, if it fits into a u64
+pub(crate) fn to_u64(x: &Fr) -> Option<u64> {
+    let repr = x.into_repr();
+    let limbs: &[u64] = repr.as_ref();
+    if limbs[1..].iter().any(|limb| *limb != 0) {
+        return None;
+    }
+    Some(limbs[0])
 }
 
 impl Add for MultiSet {
@@ -153,6 +447,16 @@ impl Mul<Fr> for &MultiSet {
         MultiSet(result)
     }
 }
+impl From<Vec<Fr>> for MultiSet {
+    fn from(elements: Vec<Fr>) -> MultiSet {
+        MultiSet(elements)
+    }
+}
+impl From<MultiSet> for Vec<Fr> {
+    fn from(set: MultiSet) -> Vec<Fr> {
+        set.0
+    }
+}
 #[cfg(test)]
 mod test {
     use super::*;
@@ -238,7 +542,7 @@ mod test {
         a.push(Fr::from(6u64));
         a.push(Fr::from(7u64));
 
-        let (h_1, h_2) = a.halve();
+        let (h_1, h_2) = a.halve().unwrap();
         assert_eq!(h_1.len(), 4);
         assert_eq!(h_2.len(), 4);
 
@@ -304,6 +608,20 @@ mod test {
         assert!(!c.is_subset_of(&a));
     }
     #[test]
+    fn test_is_subset_multiplicities() {
+        let ones = MultiSet::from_u64s(&[1, 1]);
+
+        assert!(!ones.is_subset_of(&MultiSet::from_u64s(&[1, 2])));
+        assert!(ones.is_subset_of(&MultiSet::from_u64s(&[1, 1, 2])));
+        assert!(ones.is_subset_of(&MultiSet::from_u64s(&[2, 1, 3, 1])));
+
+        // The empty set is a subset of every set, including itself
+        let empty = MultiSet::new();
+        assert!(empty.is_subset_of(&empty));
+        assert!(empty.is_subset_of(&ones));
+        assert!(!ones.is_subset_of(&empty));
+    }
+    #[test]
     fn test_sorted_by() {
         let a = MultiSet(vec![
             Fr::from(50u64),
@@ -355,4 +673,369 @@ mod test {
         assert_eq!(g.sorted_by(&f), true);
         assert_eq!(f.sorted_by(&g), false);
     }
+
+    #[test]
+    fn test_from_iter_with_capacity() {
+        let cap = 16;
+        let a = MultiSet::from_iter_with_capacity((0..cap as u64).map(Fr::from), cap);
+        assert_eq!(a.len(), cap);
+        assert_eq!(a.0.capacity(), cap);
+        assert_eq!(a.0[3], Fr::from(3u64));
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut a = MultiSet(vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]);
+        let expected = MultiSet(vec![Fr::from(3u64), Fr::from(2u64), Fr::from(1u64)]);
+
+        let b = a.reversed();
+        assert_eq!(b, expected);
+        assert_ne!(a, expected);
+
+        a.reverse();
+        assert_eq!(a, expected);
+    }
+
+    #[test]
+    fn test_boundary() {
+        let a = MultiSet::new();
+        assert_eq!(a.boundary(), Err(MultiSetError::Empty));
+
+        let b = MultiSet(vec![Fr::from(1u64)]);
+        assert_eq!(b.boundary(), Ok((Fr::from(1u64), Fr::from(1u64))));
+
+        let c = MultiSet(vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]);
+        assert_eq!(c.boundary(), Ok((Fr::from(1u64), Fr::from(3u64))));
+    }
+
+    #[test]
+    fn test_concat_with_tag() {
+        let a = MultiSet(vec![Fr::from(1u64), Fr::from(2u64)]);
+        let b = MultiSet(vec![Fr::from(2u64), Fr::from(3u64)]);
+
+        // Without a tag, the value 2 from `a` and the value 2 from `b` are indistinguishable
+        let untagged = a.concatenate(&b);
+        assert_eq!(
+            untagged.0.iter().filter(|x| **x == Fr::from(2u64)).count(),
+            2
+        );
+
+        let tagged = a.concat_with_tag(&b, Fr::from(100u64));
+        let expected = MultiSet(vec![
+            Fr::from(1u64),
+            Fr::from(2u64),
+            Fr::from(102u64),
+            Fr::from(103u64),
+        ]);
+        assert_eq!(tagged, expected);
+        assert_eq!(tagged.0.iter().filter(|x| **x == Fr::from(2u64)).count(), 1);
+    }
+
+    #[test]
+    fn test_sort_with_perm() {
+        let a = MultiSet::from_u64s(&[5, 3, 8, 3, 1]);
+
+        let (sorted, perm) = a.sort_with_perm();
+        assert_eq!(sorted, a.sort());
+        assert_eq!(perm, vec![3, 1, 4, 2, 0]);
+
+        // Applying the permutation to the original order reproduces the sorted order
+        let mut permuted = vec![Fr::zero(); a.len()];
+        for (index, element) in a.0.iter().enumerate() {
+            permuted[perm[index]] = *element;
+        }
+        assert_eq!(MultiSet(permuted), sorted);
+
+        let (sorted, perm) = MultiSet::new().sort_with_perm();
+        assert_eq!(sorted.len(), 0);
+        assert!(perm.is_empty());
+    }
+    #[test]
+    fn test_sort_by_position_in() {
+        let t = MultiSet::from_u64s(&[7, 2, 9, 4, 4]);
+        let s = MultiSet::from_u64s(&[4, 9, 7, 2, 100, 7, 4, 3]);
+
+        let sorted = s.sort_by_position_in(&t);
+        assert_eq!(sorted, MultiSet::from_u64s(&[7, 7, 2, 9, 4, 4, 3, 100]));
+        assert!(MultiSet::from_u64s(&[7, 7, 2, 9, 4, 4]).sorted_by(&t));
+
+        // Sorting by a sorted table is the same as sorting canonically
+        let s = MultiSet::from_u64s(&[4, 9, 7, 2, 7]);
+        assert_eq!(s.sort_by_position_in(&t.sort()), s.sort());
+    }
+    #[test]
+    fn test_from_rows() {
+        let rows = vec![
+            (Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)),
+            (Fr::from(4u64), Fr::from(5u64), Fr::from(6u64)),
+            (Fr::from(7u64), Fr::from(8u64), Fr::from(9u64)),
+        ];
+
+        let (a, b, c) = MultiSet::from_rows(&rows);
+        assert_eq!(a, MultiSet::from_u64s(&[1, 4, 7]));
+        assert_eq!(b, MultiSet::from_u64s(&[2, 5, 8]));
+        assert_eq!(c, MultiSet::from_u64s(&[3, 6, 9]));
+
+        let (a, b, c) = MultiSet::from_rows(&[]);
+        assert_eq!(a.len() + b.len() + c.len(), 0);
+    }
+
+    #[test]
+    fn test_fold() {
+        let a = MultiSet(vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]);
+        let gamma = Fr::from(10u64);
+
+        // Running product of (gamma + a_i)
+        let product = a.fold(Fr::one(), |acc, x| acc * (gamma + x));
+
+        let mut expected = Fr::one();
+        for x in a.0.iter() {
+            expected = expected * (gamma + x);
+        }
+        assert_eq!(product, expected);
+        assert_eq!(product, Fr::from(11u64 * 12 * 13));
+
+        // The accumulator does not need to be a field element
+        assert_eq!(a.fold(0usize, |count, _| count + 1), a.len());
+    }
+
+    #[test]
+    fn test_try_for_each() {
+        let a = MultiSet::from_u64s(&[1, 2, 3, 20, 4, 30]);
+
+        // Every element must be smaller than 10
+        let mut visited = 0;
+        let result = a.try_for_each(|x| {
+            visited += 1;
+            if *x < Fr::from(10u64) {
+                Ok(())
+            } else {
+                Err(*x)
+            }
+        });
+        assert_eq!(result, Err(Fr::from(20u64)));
+        assert_eq!(visited, 4);
+
+        let result: Result<(), ()> = a.try_for_each(|_| Ok(()));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_run_length() {
+        let a = MultiSet(vec![
+            Fr::from(1u64),
+            Fr::from(1u64),
+            Fr::from(2u64),
+            Fr::from(3u64),
+            Fr::from(3u64),
+            Fr::from(3u64),
+        ]);
+        assert_eq!(
+            a.run_length(),
+            vec![
+                (Fr::from(1u64), 2),
+                (Fr::from(2u64), 1),
+                (Fr::from(3u64), 3)
+            ]
+        );
+
+        assert!(MultiSet::new().run_length().is_empty());
+    }
+
+    #[test]
+    fn test_to_u64s() {
+        let values = vec![0u64, 1, 15, u64::MAX];
+        let a = MultiSet::from_u64s(&values);
+        assert_eq!(a.to_u64s(), Ok(values));
+
+        let bigints = a.to_bigints();
+        assert_eq!(bigints.len(), 4);
+        assert_eq!(bigints[3], BigInteger256::from(u64::MAX));
+
+        // 2^64 does not fit into a u64
+        let mut b = MultiSet::from_u64s(&[1, 2]);
+        b.push(Fr::from(u64::MAX) + Fr::one());
+        assert_eq!(b.to_u64s(), Err(MultiSetError::Overflow { index: 2 }));
+
+        // -1 is the largest field element
+        let c = MultiSet(vec![-Fr::one()]);
+        assert_eq!(c.to_u64s(), Err(MultiSetError::Overflow { index: 0 }));
+    }
+
+    #[test]
+    fn test_append() {
+        let mut a = MultiSet::from_u64s(&[1, 2]);
+        let mut b = MultiSet::from_u64s(&[3, 4]);
+
+        a.append(&mut b);
+        assert_eq!(a, MultiSet::from_u64s(&[1, 2, 3, 4]));
+        assert_eq!(b.len(), 0);
+
+        a.extend_owned(MultiSet::from_u64s(&[5]));
+        assert_eq!(a, MultiSet::from_u64s(&[1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn test_linear_combination() {
+        let a = MultiSet::from_u64s(&[1, 2, 3]);
+        let b = MultiSet::from_u64s(&[4, 5, 6]);
+        let c = MultiSet::from_u64s(&[7, 8, 9]);
+        let k = Fr::from(10u64);
+
+        // Powers of a single challenge is the same as `aggregate`
+        let combined = MultiSet::linear_combination(vec![&a, &b, &c], &[Fr::one(), k, k * k]);
+        assert_eq!(combined, MultiSet::aggregate(vec![&a, &b, &c], k));
+        assert_eq!(combined, MultiSet::from_u64s(&[741, 852, 963]));
+
+        let combined = MultiSet::linear_combination(
+            vec![&a, &b, &c],
+            &[Fr::one(), Fr::from(2u64), Fr::from(3u64)],
+        );
+        assert_eq!(combined, MultiSet::from_u64s(&[30, 36, 42]));
+    }
+    #[test]
+    fn test_aggregate3() {
+        let mut rng = rand::thread_rng();
+        let a = MultiSet::random(8, &mut rng);
+        let b = MultiSet::random(8, &mut rng);
+        let c = MultiSet::random(8, &mut rng);
+        let k = Fr::rand(&mut rng);
+
+        assert_eq!(
+            MultiSet::aggregate3(&a, &b, &c, k),
+            MultiSet::aggregate(vec![&a, &b, &c], k)
+        );
+
+        let coefficients = [Fr::from(2u64), Fr::from(3u64), Fr::from(5u64)];
+        assert_eq!(
+            MultiSet::linear_combination3(&a, &b, &c, &coefficients),
+            MultiSet::linear_combination(vec![&a, &b, &c], &coefficients)
+        );
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        use algebra::{to_bytes, ToBytes};
+        use algebra_core::biginteger::BigInteger;
+
+        let x = Fr::from(5u64);
+
+        // The same element, with the modulus added to its limbs
+        let mut limbs = x.0;
+        limbs.add_nocarry(&FrParameters::MODULUS);
+        let y = Fr::new(limbs);
+        assert_eq!(y + Fr::zero(), x);
+
+        let a = MultiSet(vec![x, Fr::from(7u64)]);
+        let mut b = MultiSet(vec![y, Fr::from(7u64)]);
+        assert_ne!(a, b);
+
+        b.canonicalize();
+        assert_eq!(a, b);
+        for (a_i, b_i) in a.0.iter().zip(b.0.iter()) {
+            assert_eq!(to_bytes![a_i].unwrap(), to_bytes![b_i].unwrap());
+        }
+    }
+    #[test]
+    fn test_random_subset_of() {
+        use rand_chacha::ChaChaRng;
+        use rand_core::SeedableRng;
+
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let table = MultiSet::random(16, &mut rng);
+        assert_eq!(table.len(), 16);
+
+        for len in 0..=table.len() {
+            let subset = MultiSet::random_subset_of(&table, len, &mut rng);
+            assert_eq!(subset.len(), len);
+            assert!(subset.is_subset_of(&table));
+        }
+    }
+    #[test]
+    fn test_difference() {
+        let a = MultiSet::from_u64s(&[1, 1, 2, 3, 3, 3]);
+        let b = MultiSet::from_u64s(&[1, 3, 3, 4]);
+
+        assert_eq!(a.difference(&b), MultiSet::from_u64s(&[1, 2, 3]));
+        assert_eq!(b.difference(&a), MultiSet::from_u64s(&[4]));
+        assert_eq!(a.difference(&a), MultiSet::new());
+        assert_eq!(a.difference(&MultiSet::new()), a);
+    }
+    #[test]
+    fn test_try_difference() {
+        let a = MultiSet::from_u64s(&[1, 1, 2, 3, 3, 3]);
+        let b = MultiSet::from_u64s(&[1, 3, 3]);
+        let c = MultiSet::from_u64s(&[1, 2, 2]);
+
+        assert_eq!(a.try_difference(&b), Ok(MultiSet::from_u64s(&[1, 2, 3])));
+        assert_eq!(
+            a.try_difference(&c),
+            Err(MultiSetError::Underflow(Fr::from(2u64)))
+        );
+        assert_eq!(
+            b.try_difference(&a),
+            Err(MultiSetError::Underflow(Fr::from(1u64)))
+        );
+    }
+    #[test]
+    fn test_try_extend_canonical() {
+        use algebra_core::biginteger::BigInteger;
+
+        // A non-canonical representation of 5
+        let mut limbs = Fr::from(5u64).0;
+        limbs.add_nocarry(&FrParameters::MODULUS);
+        let non_canonical = Fr::new(limbs);
+
+        let mut a = MultiSet::from_u64s(&[1, 2]);
+        a.try_extend_canonical(&[Fr::from(3u64), Fr::from(4u64)])
+            .unwrap();
+        assert_eq!(a, MultiSet::from_u64s(&[1, 2, 3, 4]));
+
+        // One bad element means that none of the batch is added
+        let batch = vec![Fr::from(5u64), non_canonical, Fr::from(6u64)];
+        assert_eq!(
+            a.try_extend_canonical(&batch),
+            Err(MultiSetError::NonCanonical { index: 1 })
+        );
+        assert_eq!(a, MultiSet::from_u64s(&[1, 2, 3, 4]));
+    }
+    #[test]
+    fn test_vec_conversions() {
+        let elements = vec![Fr::from(3u64), Fr::from(1u64), Fr::from(2u64)];
+
+        let a = MultiSet::from(elements.clone());
+        assert_eq!(a.as_slice(), &elements[..]);
+
+        let b: Vec<Fr> = a.into();
+        assert_eq!(b, elements);
+
+        let c: MultiSet = b.into();
+        assert_eq!(c, MultiSet::from_u64s(&[3, 1, 2]));
+        assert!(MultiSet::from(Vec::new()).as_slice().is_empty());
+    }
+    #[test]
+    fn test_halve_small_sets() {
+        assert_eq!(MultiSet::new().halve(), Err(MultiSetError::Empty));
+        assert_eq!(
+            MultiSet::from_u64s(&[1]).halve(),
+            Err(MultiSetError::SingleElement)
+        );
+
+        // The smallest valid set has n = 1
+        let (h_1, h_2) = MultiSet::from_u64s(&[1, 2, 3]).halve().unwrap();
+        assert_eq!(h_1, MultiSet::from_u64s(&[1, 2]));
+        assert_eq!(h_2, MultiSet::from_u64s(&[2, 3]));
+    }
+    #[test]
+    fn test_eval_at_index() {
+        let a = MultiSet::from_u64s(&[5, 6, 7, 8]);
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(a.len()).unwrap();
+        let poly = a.to_polynomial(&domain);
+
+        for (i, element) in domain.elements().enumerate() {
+            assert_eq!(a.eval_at_index(i), Some(poly.evaluate(element)));
+        }
+        assert_eq!(a.eval_at_index(4), None);
+        assert_eq!(MultiSet::new().eval_at_index(0), None);
+    }
 }
@@ -1,8 +1,10 @@
+use crate::commitment::{CommitKey, KZG10Scheme, PolynomialCommitment};
 use crate::kzg10;
+use crate::lookup_table::SortOrder;
 use crate::multiset::MultiSet;
-use crate::proof::{Commitments, Evaluations, MultiSetEqualityProof};
+use crate::proof::{Commitments, Evaluations, MultiSetEqualityProof, PublicEqualityProof};
 use crate::quotient_poly;
-use crate::transcript::TranscriptProtocol;
+use crate::transcript::{labels, TranscriptProtocol};
 use algebra::bls12_381::Fr;
 use algebra::Bls12_381;
 use ff_fft::{DensePolynomial as Polynomial, EvaluationDomain};
@@ -10,15 +12,26 @@ use num_traits::identities::One;
 use poly_commit::kzg10::Powers;
 /// Computes the multisets h_1 and h_2
 pub fn compute_h1_h2(f: &MultiSet, t: &MultiSet) -> (MultiSet, MultiSet) {
+    compute_h1_h2_with_order(f, t, SortOrder::Canonical)
+}
+
+/// Computes the multisets h_1 and h_2, sorting (f, t) by t using `sort_order`
+pub fn compute_h1_h2_with_order(
+    f: &MultiSet,
+    t: &MultiSet,
+    sort_order: SortOrder,
+) -> (MultiSet, MultiSet) {
     //
     // 1. Compute s
     // XXX: check if s is (f,t) sorted by t? (Tests will fail anyways according to the proof, so may be better to skip)
-    let s = f.concatenate(&t).sort();
+    let s = sort_order.sort_by_table(&f.concatenate(&t), t);
 
     //2 . Compute h_1 and h_2
-    let (h_1, h_2) = s.halve();
+    let (h_1, h_2) = s.halve().unwrap();
     // assert that the last element of h_1 is equal to the first element of h_2
-    assert_eq!(h_1.0.last().unwrap(), &h_2.0[0]);
+    let (_, h_1_last) = h_1.boundary().unwrap();
+    let (h_2_first, _) = h_2.boundary().unwrap();
+    assert_eq!(h_1_last, h_2_first);
     (h_1, h_2)
 }
 
@@ -91,60 +104,155 @@ pub fn compute_accumulator_values(
     evaluations
 }
 
+/// The grand product accumulator Z(X) from the plookup paper
+/// Z(g) = 1, and each following value multiplies in the next term of F(beta, gamma) / G(beta, gamma)
+/// For an honest prover, Z(X) returns to 1 at the last element of the domain
+#[derive(Debug, Clone)]
+pub struct Accumulator<PC: PolynomialCommitment = KZG10Scheme> {
+    /// Values of Z(X) over the domain, where the i'th value is Z(g^{i+1})
+    pub evaluations: Vec<Fr>,
+    /// Commitment to Z(X), this is the same as the commitment in the proof
+    pub commitment: PC::Commitment,
+}
+
 pub fn prove(
     f: MultiSet,
     t: MultiSet,
     proving_key: &Powers<Bls12_381>,
     transcript: &mut dyn TranscriptProtocol,
 ) -> MultiSetEqualityProof {
+    let coset_shift = quotient_poly::default_coset_shift();
+    prove_with_accumulator(f, t, proving_key, coset_shift, transcript).0
+}
+
+/// Creates a multiset equality proof and also returns the accumulator Z(X) used in the proof
+/// so that it can be folded into another grand product argument
+/// The quotient polynomial is computed on the coset `coset_shift * H_4n`, see `quotient_poly::compute_on_coset`
+pub fn prove_with_accumulator(
+    f: MultiSet,
+    t: MultiSet,
+    proving_key: &Powers<Bls12_381>,
+    coset_shift: Fr,
+    transcript: &mut dyn TranscriptProtocol,
+) -> (MultiSetEqualityProof, Accumulator) {
+    prove_with_sort_order(
+        f,
+        t,
+        proving_key,
+        coset_shift,
+        SortOrder::Canonical,
+        transcript,
+    )
+}
+
+/// Creates a multiset equality proof like `prove_with_accumulator`, where `t` is ordered as required by `sort_order`
+/// and (f, t) is sorted by `t` using `sort_order`
+pub fn prove_with_sort_order(
+    f: MultiSet,
+    t: MultiSet,
+    proving_key: &Powers<Bls12_381>,
+    coset_shift: Fr,
+    sort_order: SortOrder,
+    transcript: &mut dyn TranscriptProtocol,
+) -> (MultiSetEqualityProof, Accumulator) {
+    prove_with_scheme::<KZG10Scheme, _>(f, t, proving_key, coset_shift, sort_order, transcript)
+}
+
+/// Creates a multiset equality proof like `prove_with_sort_order`, using the commitment scheme `PC`
+pub fn prove_with_scheme<PC: PolynomialCommitment, K: CommitKey<PC>>(
+    f: MultiSet,
+    t: MultiSet,
+    commit_key: &K,
+    coset_shift: Fr,
+    sort_order: SortOrder,
+    transcript: &mut dyn TranscriptProtocol,
+) -> (MultiSetEqualityProof<PC>, Accumulator<PC>) {
     let domain: EvaluationDomain<Fr> = EvaluationDomain::new(f.len()).unwrap();
 
-    // Convert witness and table to polynomials
+    // Convert witness to a polynomial
     let f_poly = f.to_polynomial(&domain);
-    let f_commit = kzg10::commit(proving_key, &f_poly);
+    let f_commit = commit_key.commit(&f_poly);
+
+    prove_with_witness_polynomial(
+        f,
+        f_poly,
+        f_commit,
+        t,
+        None,
+        commit_key,
+        coset_shift,
+        sort_order,
+        transcript,
+    )
+}
+
+// Creates the proof given the witness polynomial and its commitment, which may have been computed ahead of time
+// If the commitment to the table is already known, for example from the commitments to its columns, it is given as `t_commit`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn prove_with_witness_polynomial<PC: PolynomialCommitment, K: CommitKey<PC>>(
+    f: MultiSet,
+    f_poly: Polynomial<Fr>,
+    f_commit: PC::Commitment,
+    t: MultiSet,
+    t_commit: Option<PC::Commitment>,
+    commit_key: &K,
+    coset_shift: Fr,
+    sort_order: SortOrder,
+    transcript: &mut dyn TranscriptProtocol,
+) -> (MultiSetEqualityProof<PC>, Accumulator<PC>) {
+    let domain: EvaluationDomain<Fr> = EvaluationDomain::new(f.len()).unwrap();
 
+    // Convert table to a polynomial
     let t_poly = t.to_polynomial(&domain);
-    let t_commit = kzg10::commit(proving_key, &t_poly);
+    let t_commit = t_commit.unwrap_or_else(|| commit_key.commit(&t_poly));
 
     // Compute h_1 and h_2
-    let (h_1, h_2) = compute_h1_h2(&f, &t);
+    let (h_1, h_2) = compute_h1_h2_with_order(&f, &t, sort_order);
 
     // Convert h_1 and h_2 to polynomials
     let h_1_poly = h_1.to_polynomial(&domain);
     let h_2_poly = h_2.to_polynomial(&domain);
 
     // Commit to h_1(X) and h_2(X)
-    let h_1_commit = kzg10::commit(proving_key, &h_1_poly);
-    let h_2_commit = kzg10::commit(proving_key, &h_2_poly);
+    let h_1_commit = commit_key.commit(&h_1_poly);
+    let h_2_commit = commit_key.commit(&h_2_poly);
 
     // Add commitments to transcript
-    transcript.append_commitment(b"h_1_poly", &h_1_commit);
-    transcript.append_commitment(b"h_2_poly", &h_2_commit);
+    PC::append_commitment(transcript, labels::H_1_POLY, &h_1_commit);
+    PC::append_commitment(transcript, labels::H_2_POLY, &h_2_commit);
 
-    let beta = transcript.challenge_scalar(b"beta");
-    let gamma = transcript.challenge_scalar(b"gamma");
+    let beta = transcript.challenge_scalar(labels::BETA);
+    let gamma = transcript.challenge_scalar(labels::GAMMA);
 
     // Compute Z(X)
     let z_evaluations = compute_accumulator_values(&f, &t, &h_1, &h_2, beta, gamma);
     let z_poly = Polynomial::from_coefficients_vec(domain.ifft(&z_evaluations));
 
     // Commit to Z(X)
-    let z_commit = kzg10::commit(proving_key, &z_poly);
-    transcript.append_commitment(b"accumulator_poly", &z_commit);
+    let z_commit = commit_key.commit(&z_poly);
+    PC::append_commitment(transcript, labels::ACCUMULATOR_POLY, &z_commit);
 
     // Compute quotient polynomial
-    let (quotient_poly, _) = quotient_poly::compute(
-        &domain, &z_poly, &f_poly, &t_poly, &h_1_poly, &h_2_poly, beta, gamma,
+    let quotient_poly = quotient_poly::compute_on_coset(
+        &domain,
+        coset_shift,
+        &z_poly,
+        &f_poly,
+        &t_poly,
+        &h_1_poly,
+        &h_2_poly,
+        beta,
+        gamma,
     );
 
     // Commit to quotient polynomial
-    let q_commit = kzg10::commit(proving_key, &quotient_poly);
-    transcript.append_commitment(b"quotient_poly", &q_commit);
+    let q_commit = commit_key.commit(&quotient_poly);
+    PC::append_commitment(transcript, labels::QUOTIENT_POLY, &q_commit);
 
     // Compute the Witness that f was a subset of t
     //
-    let evaluation_challenge = transcript.challenge_scalar(b"evaluation_challenge");
-    transcript.append_scalar(b"evaluation_challenge", &evaluation_challenge);
+    let evaluation_challenge = transcript.challenge_scalar(labels::EVALUATION_CHALLENGE);
+    transcript.append_scalar(labels::EVALUATION_CHALLENGE, &evaluation_challenge);
     let evaluation_omega = evaluation_challenge * domain.group_gen;
 
     // Compute evaluations at `z`
@@ -161,22 +269,22 @@ pub fn prove(
     let h_2_omega_eval = h_2_poly.evaluate(evaluation_omega);
     let z_omega_eval = z_poly.evaluate(evaluation_omega);
 
-    transcript.append_scalar(b"f_eval", &f_eval);
-    transcript.append_scalar(b"t_eval", &t_eval);
-    transcript.append_scalar(b"h_1_eval", &h_1_eval);
-    transcript.append_scalar(b"h_2_eval", &h_2_eval);
-    transcript.append_scalar(b"z_eval", &z_eval);
-    transcript.append_scalar(b"q_eval", &q_eval);
-    transcript.append_scalar(b"t_omega_eval", &t_omega_eval);
-    transcript.append_scalar(b"h_1_omega_eval", &h_1_omega_eval);
-    transcript.append_scalar(b"h_2_omega_eval", &h_2_omega_eval);
-    transcript.append_scalar(b"z_omega_eval", &z_omega_eval);
+    transcript.append_scalar(labels::F_EVAL, &f_eval);
+    transcript.append_scalar(labels::T_EVAL, &t_eval);
+    transcript.append_scalar(labels::H_1_EVAL, &h_1_eval);
+    transcript.append_scalar(labels::H_2_EVAL, &h_2_eval);
+    transcript.append_scalar(labels::Z_EVAL, &z_eval);
+    transcript.append_scalar(labels::Q_EVAL, &q_eval);
+    transcript.append_scalar(labels::T_OMEGA_EVAL, &t_omega_eval);
+    transcript.append_scalar(labels::H_1_OMEGA_EVAL, &h_1_omega_eval);
+    transcript.append_scalar(labels::H_2_OMEGA_EVAL, &h_2_omega_eval);
+    transcript.append_scalar(labels::Z_OMEGA_EVAL, &z_omega_eval);
 
-    let aggregation_challenge = transcript.challenge_scalar(b"witness_aggregation");
+    let aggregation_challenge = transcript.challenge_scalar(labels::WITNESS_AGGREGATION);
 
     // Compute opening proof for f(X) evaluated at `z`
-    let agg_witness = kzg10::compute_aggregate_witness(
-        vec![
+    let agg_witness_comm = commit_key.open(
+        &[
             &f_poly,
             &t_poly,
             &h_1_poly,
@@ -187,17 +295,15 @@ pub fn prove(
         evaluation_challenge,
         aggregation_challenge,
     );
-    let agg_witness_comm = kzg10::commit(proving_key, &agg_witness);
 
     // Compute opening proofs for f(X) evaluated at `z * omega`
-    let shifted_agg_witness = kzg10::compute_aggregate_witness(
-        vec![&t_poly, &h_1_poly, &h_2_poly, &z_poly],
+    let shifted_agg_witness_comm = commit_key.open(
+        &[&t_poly, &h_1_poly, &h_2_poly, &z_poly],
         evaluation_omega,
         aggregation_challenge,
     );
-    let shifted_agg_witness_comm = kzg10::commit(proving_key, &shifted_agg_witness);
 
-    MultiSetEqualityProof {
+    let proof = MultiSetEqualityProof {
         n: domain.size(),
         evaluations: Evaluations {
             f: f_eval,
@@ -220,6 +326,41 @@ pub fn prove(
         },
         aggregate_witness_comm: agg_witness_comm,
         shifted_aggregate_witness_comm: shifted_agg_witness_comm,
+    };
+
+    let accumulator = Accumulator {
+        evaluations: z_evaluations,
+        commitment: z_commit,
+    };
+
+    (proof, accumulator)
+}
+
+/// Creates a proof that the committed multiset is equal to a multiset which is known to the verifier
+/// The multiset is treated as the evaluations of a polynomial over its domain, and the polynomial is opened
+/// at a random point. The verifier computes the evaluation at that point from the public multiset,
+/// so the elements must be in the same order as in the public multiset
+pub fn prove_equals_public(
+    committed: MultiSet,
+    proving_key: &Powers<Bls12_381>,
+    transcript: &mut dyn TranscriptProtocol,
+) -> PublicEqualityProof {
+    let domain: EvaluationDomain<Fr> = EvaluationDomain::new(committed.len()).unwrap();
+
+    // Bind the size of the domain to the proof
+    transcript.append_scalar(labels::DOMAIN_SIZE, &Fr::from(domain.size() as u64));
+
+    let poly = committed.to_polynomial(&domain);
+    let commitment = kzg10::commit(proving_key, &poly);
+    transcript.append_commitment(labels::PUBLIC_POLY, &commitment);
+
+    let evaluation_challenge = transcript.challenge_scalar(labels::EVALUATION_CHALLENGE);
+    let witness = kzg10::compute_witness(&poly, evaluation_challenge);
+
+    PublicEqualityProof {
+        n: domain.size(),
+        commitment,
+        witness_comm: kzg10::commit(proving_key, &witness),
     }
 }
 
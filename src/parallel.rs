@@ -0,0 +1,182 @@
+use algebra::bls12_381::Fr;
+use algebra::UniformRand;
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The default number of elements at which sorting switches from serial to parallel
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 1 << 14;
+/// The default number of elements at which aggregating multisets switches from serial to parallel
+pub const DEFAULT_AGGREGATE_THRESHOLD: usize = 1 << 14;
+
+// Sizes that `measure` measures, from smallest to largest
+const MIN_TUNE_SIZE: usize = 1 << 8;
+const MAX_TUNE_SIZE: usize = 1 << 16;
+// Number of times each size is measured, the fastest run is kept
+const TUNE_RUNS: usize = 3;
+
+static PARALLEL_THRESHOLD: AtomicUsize = AtomicUsize::new(DEFAULT_PARALLEL_THRESHOLD);
+static AGGREGATE_THRESHOLD: AtomicUsize = AtomicUsize::new(DEFAULT_AGGREGATE_THRESHOLD);
+
+/// Returns the number of elements at which sorting switches from serial to parallel
+pub fn parallel_threshold() -> usize {
+    PARALLEL_THRESHOLD.load(Ordering::Relaxed)
+}
+
+/// Sets the number of elements at which sorting switches from serial to parallel
+pub fn set_parallel_threshold(threshold: usize) {
+    PARALLEL_THRESHOLD.store(threshold, Ordering::Relaxed)
+}
+
+/// Returns the number of elements at which aggregating multisets switches from serial to parallel
+pub fn aggregate_threshold() -> usize {
+    AGGREGATE_THRESHOLD.load(Ordering::Relaxed)
+}
+
+/// Sets the number of elements at which aggregating multisets switches from serial to parallel
+pub fn set_aggregate_threshold(threshold: usize) {
+    AGGREGATE_THRESHOLD.store(threshold, Ordering::Relaxed)
+}
+
+/// Sorts the elements in place, in parallel if there are at least `parallel_threshold()` of them
+pub(crate) fn sort(elements: &mut [Fr]) {
+    if elements.len() >= parallel_threshold() {
+        elements.par_sort()
+    } else {
+        elements.sort()
+    }
+}
+
+/// Adds `coefficient * set` to `result` element-wise, in parallel if there are at least `aggregate_threshold()` elements
+/// `result` is first truncated to the length of `set`, like adding two multisets
+pub(crate) fn add_scaled(result: &mut Vec<Fr>, set: &[Fr], coefficient: Fr) {
+    result.truncate(set.len());
+    if result.len() >= aggregate_threshold() {
+        add_scaled_parallel(result, set, coefficient)
+    } else {
+        add_scaled_serial(result, set, coefficient)
+    }
+}
+
+fn add_scaled_serial(result: &mut [Fr], set: &[Fr], coefficient: Fr) {
+    for (r, x) in result.iter_mut().zip(set.iter()) {
+        *r += &(coefficient * x);
+    }
+}
+
+fn add_scaled_parallel(result: &mut [Fr], set: &[Fr], coefficient: Fr) {
+    result
+        .par_iter_mut()
+        .zip(set.par_iter())
+        .for_each(|(r, x)| *r += &(coefficient * x));
+}
+
+/// The number of elements at which each operation switches from serial to parallel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    /// See `parallel_threshold`
+    pub sort: usize,
+    /// See `aggregate_threshold`
+    pub aggregate: usize,
+}
+
+/// Measures the crossover between serial and parallel sorting, and serial and parallel aggregation, on this machine.
+/// Sizes from 2^8 to 2^16 are measured, and the smallest size where the parallel operation is faster becomes its threshold.
+/// If the parallel operation is never faster, for example on a single core, its threshold is above 2^16
+/// The thresholds in use are not changed, see `tune`
+pub fn measure() -> Thresholds {
+    let mut rng = ChaChaRng::from_seed([0u8; 32]);
+    let elements: Vec<Fr> = (0..MAX_TUNE_SIZE).map(|_| Fr::rand(&mut rng)).collect();
+    let coefficient = Fr::rand(&mut rng);
+
+    Thresholds {
+        sort: crossover(&elements, |e| e.sort(), |e| e.par_sort()),
+        aggregate: crossover(
+            &elements,
+            |e| add_scaled_serial(e, &elements[..e.len()], coefficient),
+            |e| add_scaled_parallel(e, &elements[..e.len()], coefficient),
+        ),
+    }
+}
+
+/// Measures the thresholds like `measure` and installs them, so that they are used by every following sort and aggregation
+/// This takes a fraction of a second, and is not run automatically
+/// Returns the new thresholds
+pub fn tune() -> Thresholds {
+    let thresholds = measure();
+    set_parallel_threshold(thresholds.sort);
+    set_aggregate_threshold(thresholds.aggregate);
+    thresholds
+}
+
+// Returns the smallest measured size where `parallel` is faster than `serial`, or 2 * MAX_TUNE_SIZE if it never is
+fn crossover(elements: &[Fr], serial: impl Fn(&mut [Fr]), parallel: impl Fn(&mut [Fr])) -> usize {
+    let mut size = MIN_TUNE_SIZE;
+    while size <= MAX_TUNE_SIZE {
+        if fastest_run(&elements[..size], &parallel) < fastest_run(&elements[..size], &serial) {
+            return size;
+        }
+        size *= 2;
+    }
+    2 * MAX_TUNE_SIZE
+}
+
+// Returns the fastest time that `operation` takes on a copy of the elements
+fn fastest_run(elements: &[Fr], operation: impl Fn(&mut [Fr])) -> Duration {
+    (0..TUNE_RUNS)
+        .map(|_| {
+            let mut copy = elements.to_vec();
+            let start = Instant::now();
+            operation(&mut copy);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_measure() {
+        // Measuring does not install the thresholds, so other tests are not affected
+        let thresholds = measure();
+        for &threshold in [thresholds.sort, thresholds.aggregate].iter() {
+            assert!(threshold >= MIN_TUNE_SIZE && threshold <= 2 * MAX_TUNE_SIZE);
+            assert!(threshold.is_power_of_two());
+        }
+
+        // Sorting gives the same result either side of the threshold
+        let mut rng = ChaChaRng::from_seed([1u8; 32]);
+        let elements: Vec<Fr> = (0..1000).map(|_| Fr::rand(&mut rng)).collect();
+        let mut serial = elements.clone();
+        serial.sort();
+        let mut parallel = elements.clone();
+        parallel.par_sort();
+        assert_eq!(serial, parallel);
+
+        // So does aggregating
+        let coefficient = Fr::rand(&mut rng);
+        let mut serial = elements.clone();
+        add_scaled_serial(&mut serial, &elements, coefficient);
+        let mut parallel = elements.clone();
+        add_scaled_parallel(&mut parallel, &elements, coefficient);
+        assert_eq!(serial, parallel);
+    }
+    #[test]
+    fn test_tune() {
+        // Tuning installs the thresholds it measured
+        // Tests running at the same time are not affected, as the thresholds only change the speed
+        let thresholds = tune();
+        assert_eq!(parallel_threshold(), thresholds.sort);
+        assert_eq!(aggregate_threshold(), thresholds.aggregate);
+
+        set_parallel_threshold(DEFAULT_PARALLEL_THRESHOLD);
+        set_aggregate_threshold(DEFAULT_AGGREGATE_THRESHOLD);
+        assert_eq!(parallel_threshold(), DEFAULT_PARALLEL_THRESHOLD);
+        assert_eq!(aggregate_threshold(), DEFAULT_AGGREGATE_THRESHOLD);
+    }
+}
@@ -0,0 +1,44 @@
+// Re-exports the types needed to create and verify a lookup proof
+// Use with `use plookup::prelude::*;`
+pub use crate::kzg10::{max_degree, required_setup_degree, trusted_setup};
+pub use crate::lookup::{LookUp, LookUpError};
+pub use crate::lookup_table::{
+    AggregationStrategy, LookUpTable, PreProcessedTable, ReadResult, SortOrder, TableError,
+    XOR4BitTable, XORTable,
+};
+pub use crate::multiset::{MultiSet, MultiSetError};
+pub use crate::proof::{MultiSetEqualityProof, SerializationError, VerificationError};
+pub use crate::transcript::TranscriptProtocol;
+pub use algebra::bls12_381::Fr;
+pub use merlin::Transcript;
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_prelude() {
+        let (proving_key, verifier_key) = trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table: PreProcessedTable = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+        assert!(lookup.read(&(Fr::from(8u8), Fr::from(10u8))));
+        assert!(!lookup.read(&(Fr::from(16u8), Fr::from(6u8))));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof: MultiSetEqualityProof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        let bytes = proof.to_bytes();
+        assert_eq!(MultiSetEqualityProof::from_bytes(&bytes), Ok(proof));
+    }
+}
@@ -1,16 +1,26 @@
+use crate::commitment::{KZG10Scheme, OpeningClaim, OpeningKey, PolynomialCommitment};
 use crate::kzg10;
-use crate::lookup_table::PreProcessedTable;
-use crate::transcript::TranscriptProtocol;
-use algebra::bls12_381::Fr;
+use crate::lookup_table::{
+    order_and_pad, AggregationStrategy, PreProcessedTable, PreProcessedTwoOutputTable, SortOrder,
+    TableColumns,
+};
+use crate::multiset::MultiSet;
+use crate::transcript::{labels, TranscriptProtocol};
+use algebra::bls12_381::{Fr, G1Affine};
 use algebra::Bls12_381;
+use algebra_core::fields::Field;
+use algebra_core::serialize::{CanonicalDeserialize, CanonicalSerialize};
 use ff_fft::EvaluationDomain;
+use num_traits::identities::{One, Zero};
 use poly_commit::kzg10::Commitment;
 use poly_commit::kzg10::VerifierKey;
+use std::convert::TryInto;
 
 // Evaluations store the evaluations of different polynomial.
 // `t` denotes that the polynomial was evaluated at t(z) for some random evaluation challenge `z`
 // `t_omega` denotes the polynomial was evaluated at t(z * omega) where omega is the group generator
 // In the FFT context, the normal terminology is that t(z*omega) means to evaluate a polynomial at the next root of unity from `z`.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Evaluations {
     pub f: Fr,
     pub t: Fr,
@@ -23,13 +33,14 @@ pub struct Evaluations {
     pub z_omega: Fr,
 }
 // Commitments of different polynomials
-pub struct Commitments {
-    pub f: Commitment<Bls12_381>,
-    pub q: Commitment<Bls12_381>,
-    pub t: Commitment<Bls12_381>,
-    pub h_1: Commitment<Bls12_381>,
-    pub h_2: Commitment<Bls12_381>,
-    pub z: Commitment<Bls12_381>,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commitments<PC: PolynomialCommitment = KZG10Scheme> {
+    pub f: PC::Commitment,
+    pub q: PC::Commitment,
+    pub t: PC::Commitment,
+    pub h_1: PC::Commitment,
+    pub h_2: PC::Commitment,
+    pub z: PC::Commitment,
 }
 
 // In the best case, this protocol requires 4 extra G1 elements (Commitment)
@@ -45,61 +56,335 @@ pub struct Commitments {
 // q_eval which is the quotient evaluation is usually created from the prover messages
 //
 // Lastly, the Witness commitments can also be batched with the PLONK opening Proof.
-pub struct MultiSetEqualityProof {
+// The proof is generic over the commitment scheme `PC`, which is KZG10 unless stated otherwise
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiSetEqualityProof<PC: PolynomialCommitment = KZG10Scheme> {
     //Size of the domain
     // XXX: Verifier should have this value
     pub n: usize,
 
-    pub aggregate_witness_comm: Commitment<Bls12_381>,
-    pub shifted_aggregate_witness_comm: Commitment<Bls12_381>,
+    pub aggregate_witness_comm: PC::Opening,
+    pub shifted_aggregate_witness_comm: PC::Opening,
 
     pub evaluations: Evaluations,
 
-    pub commitments: Commitments,
+    pub commitments: Commitments<PC>,
+}
+
+/// Errors that can occur when verifying a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    /// The proof was created over a different domain than the verifier's preprocessed table
+    DomainSizeMismatch { proof: usize, table: usize },
+    /// The domain size of the proof is not a power of two, or is too large for the field
+    InvalidDomainSize(usize),
+    /// The aggregated table in the proof does not match the columns of the verifier's table
+    TableCommitmentMismatch,
+    /// The opening proofs did not verify
+    InvalidOpening,
+}
+
+/// The Fiat-Shamir challenges of a proof
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenges {
+    /// The coefficient of each column when aggregating the table and the witness
+    /// With `AggregationStrategy::Powers` and three columns these are [1, alpha, alpha^2]
+    pub coefficients: Vec<Fr>,
+    pub beta: Fr,
+    pub gamma: Fr,
+    /// The point `z` that the polynomials are evaluated at
+    pub evaluation_challenge: Fr,
+    /// The challenge used to aggregate the opening witnesses
+    pub aggregation_challenge: Fr,
+}
+
+impl<PC: PolynomialCommitment> MultiSetEqualityProof<PC> {
+    /// Re-derives the challenges of a proof created with the default options, over a table with three columns
+    /// The transcript must be in the same state as the one given to the verifier
+    pub fn challenges(
+        &self,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<Challenges, VerificationError> {
+        self.challenges_with_options(
+            AggregationStrategy::default(),
+            SortOrder::default(),
+            3,
+            transcript,
+        )
+    }
+    /// Re-derives the challenges of a proof whose `columns` table columns were combined using `strategy`,
+    /// and whose table was ordered using `sort_order`
+    /// Returns an error if the domain size of the proof is not a supported power of two
+    pub fn challenges_with_options(
+        &self,
+        strategy: AggregationStrategy,
+        sort_order: SortOrder,
+        columns: usize,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<Challenges, VerificationError> {
+        let domain = self.domain()?;
+        let (challenges, _) =
+            self.replay_transcript(&domain, strategy, sort_order, columns, transcript);
+        Ok(challenges)
+    }
+    // Builds the domain of the proof, which must have exactly `n` elements
+    fn domain(&self) -> Result<EvaluationDomain<Fr>, VerificationError> {
+        match EvaluationDomain::new(self.n) {
+            Some(domain) if domain.size() == self.n => Ok(domain),
+            _ => Err(VerificationError::InvalidDomainSize(self.n)),
+        }
+    }
+    // Adds the provers messages to the transcript in the same order as the prover,
+    // returning the challenges and the evaluation of the quotient polynomial, which the verifier computes itself
+    // `columns` is the number of columns in the table
+    fn replay_transcript(
+        &self,
+        domain: &EvaluationDomain<Fr>,
+        strategy: AggregationStrategy,
+        sort_order: SortOrder,
+        columns: usize,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> (Challenges, Fr) {
+        transcript.append_scalar(labels::DOMAIN_SIZE, &Fr::from(self.n as u64));
+        let coefficients = strategy.coefficients(transcript, columns);
+        sort_order.append_to_transcript(transcript);
+
+        PC::append_commitment(transcript, labels::H_1_POLY, &self.commitments.h_1);
+        PC::append_commitment(transcript, labels::H_2_POLY, &self.commitments.h_2);
+        let beta = transcript.challenge_scalar(labels::BETA);
+        let gamma = transcript.challenge_scalar(labels::GAMMA);
+        PC::append_commitment(transcript, labels::ACCUMULATOR_POLY, &self.commitments.z);
+        PC::append_commitment(transcript, labels::QUOTIENT_POLY, &self.commitments.q);
+        let evaluation_challenge = transcript.challenge_scalar(labels::EVALUATION_CHALLENGE);
+        transcript.append_scalar(labels::EVALUATION_CHALLENGE, &evaluation_challenge);
+
+        // Compute quotient evaluation (Q(z)) from the provers messages
+        let q_eval = self.compute_quotient_evaluation(&beta, &gamma, &evaluation_challenge, domain);
+
+        transcript.append_scalar(labels::F_EVAL, &self.evaluations.f);
+        transcript.append_scalar(labels::T_EVAL, &self.evaluations.t);
+        transcript.append_scalar(labels::H_1_EVAL, &self.evaluations.h_1);
+        transcript.append_scalar(labels::H_2_EVAL, &self.evaluations.h_2);
+        transcript.append_scalar(labels::Z_EVAL, &self.evaluations.z);
+        transcript.append_scalar(labels::Q_EVAL, &q_eval);
+        transcript.append_scalar(labels::T_OMEGA_EVAL, &self.evaluations.t_omega);
+        transcript.append_scalar(labels::H_1_OMEGA_EVAL, &self.evaluations.h_1_omega);
+        transcript.append_scalar(labels::H_2_OMEGA_EVAL, &self.evaluations.h_2_omega);
+        transcript.append_scalar(labels::Z_OMEGA_EVAL, &self.evaluations.z_omega);
+
+        let aggregation_challenge = transcript.challenge_scalar(labels::WITNESS_AGGREGATION);
+
+        let challenges = Challenges {
+            coefficients,
+            beta,
+            gamma,
+            evaluation_challenge,
+            aggregation_challenge,
+        };
+        (challenges, q_eval)
+    }
+    /// Verifies the proof against a table pre-processed with the commitment scheme `PC`, using its opening key
+    /// The table is checked like in `verify`, using `PolynomialCommitment::linear_combination` of the column commitments
+    pub fn verify_with_scheme<K: OpeningKey<PC>>(
+        &self,
+        opening_key: &K,
+        preprocessed_table: &PreProcessedTable<PC>,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(), VerificationError> {
+        self.verify_with_options(
+            opening_key,
+            preprocessed_table,
+            AggregationStrategy::default(),
+            SortOrder::default(),
+            transcript,
+        )
+    }
+    /// Verifies a proof against a table with any number of columns, whose columns were combined using `strategy`
+    /// and whose table was ordered using `sort_order`
+    ///
+    /// The proof is bound to the columns of the table with either order.
+    /// With `SortOrder::TablePosition` the commitment to the aggregated table in the proof is checked
+    /// against `TableColumns::aggregated_commitment`.
+    /// With `SortOrder::Canonical` the aggregated table is sorted, and its commitment can not be computed from
+    /// the column commitments. The verifier computes the sorted table with `TableColumns::aggregate_columns`,
+    /// and checks its evaluations at `z` and `z * omega` against the evaluations in the proof
+    pub fn verify_with_options<K: OpeningKey<PC>, P: TableColumns<PC>>(
+        &self,
+        opening_key: &K,
+        preprocessed_table: &P,
+        strategy: AggregationStrategy,
+        sort_order: SortOrder,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(), VerificationError> {
+        let table_commitment =
+            |coefficients: &[Fr]| preprocessed_table.aggregated_commitment(coefficients);
+        let table =
+            |coefficients: &[Fr]| preprocessed_table.aggregate_columns(coefficients, sort_order);
+        let table_check = match sort_order {
+            SortOrder::TablePosition => TableCheck::Commitment(&table_commitment),
+            SortOrder::Canonical => TableCheck::Evaluations(&table),
+        };
+        self.verify_inner(
+            opening_key,
+            preprocessed_table.n(),
+            strategy,
+            sort_order,
+            preprocessed_table.columns().len(),
+            table_check,
+            transcript,
+        )
+    }
+}
+
+// How the verifier checks that the aggregated table in the proof was computed from its table
+// Each variant is given the coefficients of the columns
+enum TableCheck<'a, PC: PolynomialCommitment> {
+    // Computes the commitment to the aggregated table, which is compared with the commitment in the proof
+    Commitment(&'a dyn Fn(&[Fr]) -> PC::Commitment),
+    // Computes the aggregated table, whose evaluations are compared with the evaluations in the proof
+    Evaluations(&'a dyn Fn(&[Fr]) -> MultiSet),
 }
 
 impl MultiSetEqualityProof {
+    /// Verifies the proof against a preprocessed table
+    /// Only the succinct verifier key is needed, the proving key is not used during verification
+    /// The aggregated table in the proof is checked against the columns of the table,
+    /// as described in `verify_with_options`
     pub fn verify(
         &self,
         verification_key: &VerifierKey<Bls12_381>,
         preprocessed_table: &PreProcessedTable,
         transcript: &mut dyn TranscriptProtocol,
-    ) -> bool {
-        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(self.n).unwrap();
-
-        let alpha = transcript.challenge_scalar(b"alpha");
-
-        transcript.append_scalar(b"alpha", &alpha);
-        transcript.append_commitment(b"h_1_poly", &self.commitments.h_1);
-        transcript.append_commitment(b"h_2_poly", &self.commitments.h_2);
-        let beta = transcript.challenge_scalar(b"beta");
-        let gamma = transcript.challenge_scalar(b"gamma");
-        transcript.append_commitment(b"accumulator_poly", &self.commitments.z);
-        transcript.append_commitment(b"quotient_poly", &self.commitments.q);
-        let evaluation_challenge = transcript.challenge_scalar(b"evaluation_challenge");
-        transcript.append_scalar(b"evaluation_challenge", &evaluation_challenge);
+    ) -> Result<(), VerificationError> {
+        self.verify_with_strategy(
+            verification_key,
+            preprocessed_table,
+            AggregationStrategy::default(),
+            transcript,
+        )
+    }
+    /// Verifies a proof whose table and witness columns were combined using `strategy`
+    /// The table is checked like in `verify`
+    pub fn verify_with_strategy(
+        &self,
+        verification_key: &VerifierKey<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+        strategy: AggregationStrategy,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(), VerificationError> {
+        self.verify_with_options(
+            verification_key,
+            preprocessed_table,
+            strategy,
+            SortOrder::default(),
+            transcript,
+        )
+    }
+    /// Verifies a proof whose table was ordered using `sort_order`
+    /// The table is checked as described in `verify_with_options`
+    pub fn verify_with_sort_order(
+        &self,
+        verification_key: &VerifierKey<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+        sort_order: SortOrder,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(), VerificationError> {
+        self.verify_with_options(
+            verification_key,
+            preprocessed_table,
+            AggregationStrategy::default(),
+            sort_order,
+            transcript,
+        )
+    }
+    /// Verifies a proof created by `TwoOutputLookUp::prove` against a table with two outputs
+    /// The table is checked like in `verify`
+    pub fn verify_two_outputs(
+        &self,
+        verification_key: &VerifierKey<Bls12_381>,
+        preprocessed_table: &PreProcessedTwoOutputTable,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(), VerificationError> {
+        self.verify_with_options(
+            verification_key,
+            preprocessed_table,
+            AggregationStrategy::default(),
+            SortOrder::default(),
+            transcript,
+        )
+    }
+    /// Verifies the proof against the raw columns of a table, which are pre-processed to a size `n`
+    /// The verifier aggregates the columns itself, and checks the evaluations of the aggregated table
+    /// at `z` and `z * omega` against the evaluations in the proof, so only the verifier key is needed
+    /// Panics if the columns can not be pre-processed, like `PreProcessedTable::new`
+    pub fn verify_with_table_columns(
+        &self,
+        verification_key: &VerifierKey<Bls12_381>,
+        t_1: MultiSet,
+        t_2: MultiSet,
+        t_3: MultiSet,
+        n: usize,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(), VerificationError> {
+        let [t_1, t_2, t_3] = order_and_pad(n, [t_1, t_2, t_3]);
+        let sort_order = SortOrder::default();
+        let table = |coefficients: &[Fr]| {
+            let columns = vec![&t_1, &t_2, &t_3];
+            sort_order.order_table(MultiSet::linear_combination(columns, coefficients))
+        };
+        self.verify_inner(
+            verification_key,
+            n,
+            AggregationStrategy::default(),
+            sort_order,
+            3,
+            TableCheck::Evaluations(&table),
+            transcript,
+        )
+    }
+}
+
+impl<PC: PolynomialCommitment> MultiSetEqualityProof<PC> {
+    // `table_n` is the size of the verifier's table and `columns` is its number of columns
+    // `table_check` binds the proof to the verifier's table
+    #[allow(clippy::too_many_arguments)]
+    fn verify_inner<K: OpeningKey<PC>>(
+        &self,
+        opening_key: &K,
+        table_n: usize,
+        strategy: AggregationStrategy,
+        sort_order: SortOrder,
+        columns: usize,
+        table_check: TableCheck<PC>,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(), VerificationError> {
+        if self.n != table_n {
+            return Err(VerificationError::DomainSizeMismatch {
+                proof: self.n,
+                table: table_n,
+            });
+        }
+        let domain = self.domain()?;
+
+        let (challenges, q_eval) =
+            self.replay_transcript(&domain, strategy, sort_order, columns, transcript);
+        let Challenges {
+            coefficients,
+            evaluation_challenge,
+            aggregation_challenge,
+            ..
+        } = challenges;
         let evaluation_omega = evaluation_challenge * domain.group_gen;
 
-        // Compute quotient evaluation (Q(z)) from the provers messages
-        let q_eval =
-            self.compute_quotient_evaluation(&beta, &gamma, &evaluation_challenge, &domain);
-
-        transcript.append_scalar(b"f_eval", &self.evaluations.f);
-        transcript.append_scalar(b"t_eval", &self.evaluations.t);
-        transcript.append_scalar(b"h_1_eval", &self.evaluations.h_1);
-        transcript.append_scalar(b"h_2_eval", &self.evaluations.h_2);
-        transcript.append_scalar(b"z_eval", &self.evaluations.z);
-        transcript.append_scalar(b"q_eval", &q_eval);
-        transcript.append_scalar(b"t_omega_eval", &self.evaluations.t_omega);
-        transcript.append_scalar(b"h_1_omega_eval", &self.evaluations.h_1_omega);
-        transcript.append_scalar(b"h_2_omega_eval", &self.evaluations.h_2_omega);
-        transcript.append_scalar(b"z_omega_eval", &self.evaluations.z_omega);
-
-        let aggregation_challenge = transcript.challenge_scalar(b"witness_aggregation");
-
-        // Create aggregate opening proof for all polynomials evaluated at the evaluation challenge `z`
-        let agg_commitment = kzg10::aggregate_commitments(
-            vec![
+        if let TableCheck::Commitment(table_commitment) = table_check {
+            if table_commitment(&coefficients) != self.commitments.t {
+                return Err(VerificationError::TableCommitmentMismatch);
+            }
+        }
+
+        // Opening proof for all polynomials evaluated at the evaluation challenge `z`
+        let claim = OpeningClaim {
+            commitments: vec![
                 &self.commitments.f,
                 &self.commitments.t,
                 &self.commitments.h_1,
@@ -107,53 +392,53 @@ impl MultiSetEqualityProof {
                 &self.commitments.z,
                 &self.commitments.q,
             ],
-            aggregation_challenge,
-        );
-        let agg_value = kzg10::aggregate_values(
-            vec![
-                &self.evaluations.f,
-                &self.evaluations.t,
-                &self.evaluations.h_1,
-                &self.evaluations.h_2,
-                &self.evaluations.z,
-                &q_eval,
+            point: evaluation_challenge,
+            values: vec![
+                self.evaluations.f,
+                self.evaluations.t,
+                self.evaluations.h_1,
+                self.evaluations.h_2,
+                self.evaluations.z,
+                q_eval,
             ],
-            aggregation_challenge,
-        );
+            opening: &self.aggregate_witness_comm,
+        };
 
-        // Create aggregate opening proof for all polynomials evaluated at the shifted evaluation challenge `z * omega`
-        let shifted_agg_commitment = kzg10::aggregate_commitments(
-            vec![
+        // Opening proof for all polynomials evaluated at the shifted evaluation challenge `z * omega`
+        let shifted_claim = OpeningClaim {
+            commitments: vec![
                 &self.commitments.t,
                 &self.commitments.h_1,
                 &self.commitments.h_2,
                 &self.commitments.z,
             ],
-            aggregation_challenge,
-        );
-        let shifted_agg_value = kzg10::aggregate_values(
-            vec![
-                &self.evaluations.t_omega,
-                &self.evaluations.h_1_omega,
-                &self.evaluations.h_2_omega,
-                &self.evaluations.z_omega,
+            point: evaluation_omega,
+            values: vec![
+                self.evaluations.t_omega,
+                self.evaluations.h_1_omega,
+                self.evaluations.h_2_omega,
+                self.evaluations.z_omega,
             ],
-            aggregation_challenge,
-        );
+            opening: &self.shifted_aggregate_witness_comm,
+        };
 
         // Batch Verify both opening proofs
-        let ok = kzg10::batch_verify(
-            &verification_key,
-            vec![agg_commitment, shifted_agg_commitment],
-            vec![
-                self.aggregate_witness_comm,
-                self.shifted_aggregate_witness_comm,
-            ],
-            vec![evaluation_challenge, evaluation_omega],
-            vec![agg_value, shifted_agg_value],
-        );
+        let ok = opening_key.verify_openings(&[claim, shifted_claim], aggregation_challenge);
 
-        ok
+        if !ok {
+            return Err(VerificationError::InvalidOpening);
+        }
+
+        // The openings are checked first, so the evaluations are known to be those of the table in the proof
+        if let TableCheck::Evaluations(table) = table_check {
+            let table = table(&coefficients);
+            let t_eval = evaluate_over_domain(&domain, &table, evaluation_challenge);
+            let t_omega_eval = evaluate_over_domain(&domain, &table, evaluation_omega);
+            if t_eval != self.evaluations.t || t_omega_eval != self.evaluations.t_omega {
+                return Err(VerificationError::TableCommitmentMismatch);
+            }
+        }
+        Ok(())
     }
     /// Computes the quotient evaluation from the prover messages
     fn compute_quotient_evaluation(
@@ -173,7 +458,7 @@ impl MultiSetEqualityProof {
         let ln_plus_1_z = lagrange_evaluations[domain.size() - 1];
 
         // Z_H(Z)
-        let v_h = domain.evaluate_vanishing_polynomial(*evaluation_challenge);
+        let v_h = vanishing_eval(domain, *evaluation_challenge);
 
         let beta_one = Fr::from(1u8) + beta;
         let gamma_beta_one = (Fr::from(1u8) + beta) * gamma;
@@ -208,3 +493,684 @@ impl MultiSetEqualityProof {
         (a + b - c + d + e) / v_h
     }
 }
+
+/// Version of the serialised proof format, written as the first byte of every serialised proof
+pub const PROOF_VERSION: u8 = 1;
+
+// Sizes of a compressed G1 element and a scalar
+const G1_SIZE: usize = 48;
+const SCALAR_SIZE: usize = 32;
+
+/// Size in bytes of a serialised proof
+pub const PROOF_SIZE: usize = 1 + 8 + 8 * G1_SIZE + 9 * SCALAR_SIZE;
+
+/// The default limit on the domain size of a deserialised proof
+pub const DEFAULT_MAX_DOMAIN_SIZE: usize = 1 << 26;
+
+/// Errors that can occur when deserialising a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerializationError {
+    /// The proof was serialised using a different version of the proof format
+    UnsupportedVersion(u8),
+    /// The buffer ended before the whole proof was read
+    UnexpectedEnd,
+    /// A group or field element was not encoded correctly
+    InvalidData,
+    /// The buffer contained more bytes than the serialised proof
+    TrailingBytes,
+    /// The proof claims a domain of size `n`, which is more than the limit `max`
+    DomainTooLarge { n: u64, max: usize },
+}
+
+impl MultiSetEqualityProof {
+    /// Serialises the proof, starting with `PROOF_VERSION`
+    /// followed by `n`, the commitments and then the evaluations, in the order they are declared
+    /// Group elements are compressed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![PROOF_VERSION];
+        bytes.extend_from_slice(&(self.n as u64).to_le_bytes());
+
+        let commitments = vec![
+            &self.aggregate_witness_comm,
+            &self.shifted_aggregate_witness_comm,
+            &self.commitments.f,
+            &self.commitments.q,
+            &self.commitments.t,
+            &self.commitments.h_1,
+            &self.commitments.h_2,
+            &self.commitments.z,
+        ];
+        for commitment in commitments {
+            commitment.0.serialize(&mut bytes).unwrap();
+        }
+
+        let evaluations = vec![
+            &self.evaluations.f,
+            &self.evaluations.t,
+            &self.evaluations.t_omega,
+            &self.evaluations.h_1,
+            &self.evaluations.h_1_omega,
+            &self.evaluations.h_2,
+            &self.evaluations.h_2_omega,
+            &self.evaluations.z,
+            &self.evaluations.z_omega,
+        ];
+        for evaluation in evaluations {
+            evaluation.serialize(&mut bytes).unwrap();
+        }
+
+        bytes
+    }
+    /// Deserialises a proof created with `to_bytes`
+    /// Returns an error if the proof was serialised using a different version,
+    /// or if the buffer is shorter or longer than a serialised proof
+    /// Proofs over a domain with more than `DEFAULT_MAX_DOMAIN_SIZE` elements are rejected,
+    /// as the verifier does work proportional to the domain size
+    pub fn from_bytes(bytes: &[u8]) -> Result<MultiSetEqualityProof, SerializationError> {
+        MultiSetEqualityProof::from_bytes_with_limit(bytes, DEFAULT_MAX_DOMAIN_SIZE)
+    }
+    /// Deserialises a proof created with `to_bytes`, allowing a domain of at most `max_domain_size` elements
+    /// Every other part of the proof has a fixed size, so the limits are checked before any element is decoded
+    /// The version is checked first, so that a proof in another version of the format is reported as such
+    pub fn from_bytes_with_limit(
+        bytes: &[u8],
+        max_domain_size: usize,
+    ) -> Result<MultiSetEqualityProof, SerializationError> {
+        let (version, mut reader) = bytes
+            .split_first()
+            .ok_or(SerializationError::UnexpectedEnd)?;
+        if *version != PROOF_VERSION {
+            return Err(SerializationError::UnsupportedVersion(*version));
+        }
+        if bytes.len() > PROOF_SIZE {
+            return Err(SerializationError::TrailingBytes);
+        }
+
+        let n = u64::from_le_bytes(read_bytes(&mut reader, 8)?.try_into().unwrap());
+        if n > max_domain_size as u64 {
+            return Err(SerializationError::DomainTooLarge {
+                n,
+                max: max_domain_size,
+            });
+        }
+        let n = n as usize;
+
+        let aggregate_witness_comm = read_commitment(&mut reader)?;
+        let shifted_aggregate_witness_comm = read_commitment(&mut reader)?;
+        let commitments = Commitments {
+            f: read_commitment(&mut reader)?,
+            q: read_commitment(&mut reader)?,
+            t: read_commitment(&mut reader)?,
+            h_1: read_commitment(&mut reader)?,
+            h_2: read_commitment(&mut reader)?,
+            z: read_commitment(&mut reader)?,
+        };
+        let evaluations = Evaluations {
+            f: read_scalar(&mut reader)?,
+            t: read_scalar(&mut reader)?,
+            t_omega: read_scalar(&mut reader)?,
+            h_1: read_scalar(&mut reader)?,
+            h_1_omega: read_scalar(&mut reader)?,
+            h_2: read_scalar(&mut reader)?,
+            h_2_omega: read_scalar(&mut reader)?,
+            z: read_scalar(&mut reader)?,
+            z_omega: read_scalar(&mut reader)?,
+        };
+        if !reader.is_empty() {
+            return Err(SerializationError::TrailingBytes);
+        }
+
+        Ok(MultiSetEqualityProof {
+            n,
+            aggregate_witness_comm,
+            shifted_aggregate_witness_comm,
+            evaluations,
+            commitments,
+        })
+    }
+}
+
+// Splits off the next `len` bytes from the reader
+fn read_bytes<'a>(reader: &mut &'a [u8], len: usize) -> Result<&'a [u8], SerializationError> {
+    if reader.len() < len {
+        return Err(SerializationError::UnexpectedEnd);
+    }
+    let (bytes, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(bytes)
+}
+
+fn read_commitment(reader: &mut &[u8]) -> Result<Commitment<Bls12_381>, SerializationError> {
+    let mut bytes = read_bytes(reader, G1_SIZE)?;
+    let point = G1Affine::deserialize(&mut bytes).map_err(|_| SerializationError::InvalidData)?;
+    Ok(Commitment(point))
+}
+
+fn read_scalar(reader: &mut &[u8]) -> Result<Fr, SerializationError> {
+    let mut bytes = read_bytes(reader, SCALAR_SIZE)?;
+    Fr::deserialize(&mut bytes).map_err(|_| SerializationError::InvalidData)
+}
+
+/// A proof that a committed multiset is equal to a public multiset, created with `multiset_equality::prove_equals_public`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicEqualityProof {
+    // Size of the domain
+    pub n: usize,
+    /// Commitment to the polynomial whose evaluations over the domain are the committed multiset
+    pub commitment: Commitment<Bls12_381>,
+    /// Opening proof for the commitment at the evaluation challenge
+    pub witness_comm: Commitment<Bls12_381>,
+}
+
+impl PublicEqualityProof {
+    /// Verifies that the committed multiset is equal to `public`, element by element
+    pub fn verify(
+        &self,
+        verification_key: &VerifierKey<Bls12_381>,
+        public: &MultiSet,
+        transcript: &mut dyn TranscriptProtocol,
+    ) -> Result<(), VerificationError> {
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(public.len()).unwrap();
+        if self.n != domain.size() {
+            return Err(VerificationError::DomainSizeMismatch {
+                proof: self.n,
+                table: domain.size(),
+            });
+        }
+
+        transcript.append_scalar(labels::DOMAIN_SIZE, &Fr::from(self.n as u64));
+        transcript.append_commitment(labels::PUBLIC_POLY, &self.commitment);
+        let evaluation_challenge = transcript.challenge_scalar(labels::EVALUATION_CHALLENGE);
+
+        // Evaluate the polynomial interpolating the public multiset at `z`
+        let value = evaluate_over_domain(&domain, public, evaluation_challenge);
+
+        let ok = kzg10::verify(
+            verification_key,
+            &self.commitment,
+            &self.witness_comm,
+            evaluation_challenge,
+            value,
+        );
+        if !ok {
+            return Err(VerificationError::InvalidOpening);
+        }
+        Ok(())
+    }
+}
+
+// Evaluates the polynomial whose evaluations over the domain are `values` at `point`
+// Elements missing from the end of the domain are zero, as in `MultiSet::to_polynomial`
+fn evaluate_over_domain(domain: &EvaluationDomain<Fr>, values: &MultiSet, point: Fr) -> Fr {
+    let lagrange_evaluations = domain.evaluate_all_lagrange_coefficients(point);
+    values
+        .as_slice()
+        .iter()
+        .zip(lagrange_evaluations.iter())
+        .fold(Fr::zero(), |acc, (element, l_i)| acc + &(*element * l_i))
+}
+
+/// Evaluates the vanishing polynomial Z_H(X) = X^n - 1 of the domain at `z`
+pub fn vanishing_eval(domain: &EvaluationDomain<Fr>, z: Fr) -> Fr {
+    z.pow(&[domain.size() as u64]) - Fr::one()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lookup::LookUp;
+    use crate::lookup_table::{LookUpTable, XOR4BitTable};
+    use algebra::bls12_381::G1Projective;
+    use algebra_core::curves::{AffineCurve, ProjectiveCurve};
+    use merlin::Transcript;
+    use poly_commit::kzg10::Powers;
+
+    #[test]
+    fn test_public_equality_proof() {
+        use crate::multiset_equality::prove_equals_public;
+
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(8), b"insecure_seed");
+
+        let public = MultiSet::from_u64s(&[4, 8, 15, 16, 23, 42]);
+        let mut prover_transcript = Transcript::new(b"public");
+        let proof = prove_equals_public(public.clone(), &proving_key, &mut prover_transcript);
+
+        let mut verifier_transcript = Transcript::new(b"public");
+        let ok = proof
+            .verify(&verifier_key, &public, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        // A different element, or the same elements in a different order, are rejected
+        for mismatched in &[
+            MultiSet::from_u64s(&[4, 8, 15, 16, 23, 43]),
+            MultiSet::from_u64s(&[8, 4, 15, 16, 23, 42]),
+        ] {
+            let mut verifier_transcript = Transcript::new(b"public");
+            let result = proof.verify(&verifier_key, mismatched, &mut verifier_transcript);
+            assert_eq!(result, Err(VerificationError::InvalidOpening));
+        }
+
+        // A public multiset over a different domain is rejected before opening
+        let longer = MultiSet::from_u64s(&[4, 8, 15, 16, 23, 42, 0, 0, 0]);
+        let mut verifier_transcript = Transcript::new(b"public");
+        let result = proof.verify(&verifier_key, &longer, &mut verifier_transcript);
+        assert_eq!(
+            result,
+            Err(VerificationError::DomainSizeMismatch {
+                proof: 8,
+                table: 16
+            })
+        );
+    }
+    #[test]
+    fn test_vanishing_eval() {
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(8).unwrap();
+        let z = Fr::from(12345u64);
+
+        // Z_H(z) = (z - w^0)(z - w^1)...(z - w^{n-1})
+        let naive = domain
+            .elements()
+            .fold(Fr::one(), |acc, element| acc * (z - element));
+
+        assert_eq!(vanishing_eval(&domain, z), naive);
+        assert_eq!(
+            vanishing_eval(&domain, z),
+            domain.evaluate_vanishing_polynomial(z)
+        );
+
+        // Z_H vanishes on every element of the domain
+        for element in domain.elements() {
+            assert_eq!(vanishing_eval(&domain, element), Fr::from(0u8));
+        }
+    }
+
+    // Adds the generator to a commitment
+    fn bump(commitment: &mut Commitment<Bls12_381>) {
+        let bumped = commitment.0.into_projective() + G1Projective::prime_subgroup_generator();
+        *commitment = Commitment(bumped.into());
+    }
+
+    // Applies `mutate` to a copy of a valid proof and checks that the verifier rejects it
+    fn assert_rejected<F: Fn(&mut MultiSetEqualityProof)>(
+        proof: &MultiSetEqualityProof,
+        verification_key: &VerifierKey<Bls12_381>,
+        preprocessed_table: &PreProcessedTable,
+        mutate: F,
+    ) {
+        let mut mutated = proof.clone();
+        mutate(&mut mutated);
+        assert_ne!(&mutated, proof);
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = mutated.verify(
+            verification_key,
+            preprocessed_table,
+            &mut verifier_transcript,
+        );
+        assert!(result.is_err());
+    }
+
+    // Creates a valid proof for three reads of the 4 bit XOR table
+    fn setup_proof() -> (
+        MultiSetEqualityProof,
+        VerifierKey<Bls12_381>,
+        PreProcessedTable,
+    ) {
+        // Setup SRS
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(2u8), Fr::from(4u8)));
+        lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        (proof, verifier_key, preprocessed_table)
+    }
+
+    // Pre-processes the 4 bit XOR table with one row changed, so that 0 XOR 0 is 1
+    fn other_xor_table(proving_key: &Powers<Bls12_381>) -> PreProcessedTable {
+        let (t_1, t_2, mut t_3) = XOR4BitTable::new().to_multiset();
+        let index = (0..t_1.len())
+            .find(|&i| t_1.0[i].is_zero() && t_2.0[i].is_zero())
+            .unwrap();
+        t_3.0[index] = Fr::one();
+        PreProcessedTable::new(proving_key, 2usize.pow(8), t_1, t_2, t_3)
+    }
+
+    #[test]
+    fn test_verify_without_proving_key() {
+        let (proving_key, verifier_key) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+
+        let table = XOR4BitTable::new();
+        let preprocessed_table = table.preprocess(&proving_key, 2usize.pow(8));
+
+        let other_table = other_xor_table(&proving_key);
+
+        let mut lookup = LookUp::new(table);
+        lookup.read(&(Fr::from(6u8), Fr::from(9u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let proof = lookup
+            .prove(&proving_key, &preprocessed_table, &mut prover_transcript)
+            .unwrap();
+
+        // The proving key is no longer available to the verifier
+        drop(proving_key);
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        // The proof is still checked against the columns of the table
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = proof.verify(&verifier_key, &other_table, &mut verifier_transcript);
+        assert_eq!(result, Err(VerificationError::TableCommitmentMismatch));
+    }
+    #[test]
+    fn test_mutated_proofs_are_rejected() {
+        let (proof, verifier_key, preprocessed_table) = setup_proof();
+
+        // The unmodified proof is accepted
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let result = proof.verify(&verifier_key, &preprocessed_table, &mut verifier_transcript);
+        assert!(result.is_ok());
+
+        let vk = &verifier_key;
+        let table = &preprocessed_table;
+        let one = Fr::one();
+
+        // Domain size
+        assert_rejected(&proof, vk, table, |p| p.n *= 2);
+
+        // Evaluations
+        assert_rejected(&proof, vk, table, |p| p.evaluations.f += &one);
+        assert_rejected(&proof, vk, table, |p| p.evaluations.t += &one);
+        assert_rejected(&proof, vk, table, |p| p.evaluations.t_omega += &one);
+        assert_rejected(&proof, vk, table, |p| p.evaluations.h_1 += &one);
+        assert_rejected(&proof, vk, table, |p| p.evaluations.h_1_omega += &one);
+        assert_rejected(&proof, vk, table, |p| p.evaluations.h_2 += &one);
+        assert_rejected(&proof, vk, table, |p| p.evaluations.h_2_omega += &one);
+        assert_rejected(&proof, vk, table, |p| p.evaluations.z += &one);
+        assert_rejected(&proof, vk, table, |p| p.evaluations.z_omega += &one);
+
+        // Commitments
+        assert_rejected(&proof, vk, table, |p| bump(&mut p.commitments.f));
+        assert_rejected(&proof, vk, table, |p| bump(&mut p.commitments.q));
+        assert_rejected(&proof, vk, table, |p| bump(&mut p.commitments.t));
+        assert_rejected(&proof, vk, table, |p| bump(&mut p.commitments.h_1));
+        assert_rejected(&proof, vk, table, |p| bump(&mut p.commitments.h_2));
+        assert_rejected(&proof, vk, table, |p| bump(&mut p.commitments.z));
+
+        // Opening proofs
+        assert_rejected(&proof, vk, table, |p| bump(&mut p.aggregate_witness_comm));
+        assert_rejected(&proof, vk, table, |p| {
+            bump(&mut p.shifted_aggregate_witness_comm)
+        });
+
+        // An honest proof of the same reads against a different table of the same size
+        // has valid openings, so only the check against the table columns can reject it
+        let (proving_key, _) = kzg10::trusted_setup(2usize.pow(12), b"insecure_seed");
+        let other_table = other_xor_table(&proving_key);
+        let mut lookup = LookUp::new(XOR4BitTable::new());
+        lookup.read(&(Fr::from(1u8), Fr::from(2u8)));
+        lookup.read(&(Fr::from(2u8), Fr::from(4u8)));
+        lookup.read(&(Fr::from(3u8), Fr::from(5u8)));
+
+        let mut prover_transcript = Transcript::new(b"lookup");
+        let other_proof = lookup
+            .prove(&proving_key, &other_table, &mut prover_transcript)
+            .unwrap();
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = other_proof
+            .verify(vk, &other_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        assert_eq!(
+            other_proof.verify(vk, table, &mut verifier_transcript),
+            Err(VerificationError::TableCommitmentMismatch)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let (proof, _, _) = setup_proof();
+
+        let mut bytes = proof.to_bytes();
+        assert_eq!(bytes[0], PROOF_VERSION);
+        assert_eq!(MultiSetEqualityProof::from_bytes(&bytes), Ok(proof));
+
+        bytes[0] = PROOF_VERSION + 1;
+        assert_eq!(
+            MultiSetEqualityProof::from_bytes(&bytes),
+            Err(SerializationError::UnsupportedVersion(PROOF_VERSION + 1))
+        );
+        assert_eq!(
+            MultiSetEqualityProof::from_bytes(&[]),
+            Err(SerializationError::UnexpectedEnd)
+        );
+
+        // The version is checked before the length, so a proof in a longer future format reports its version
+        bytes.push(0);
+        assert_eq!(
+            MultiSetEqualityProof::from_bytes(&bytes),
+            Err(SerializationError::UnsupportedVersion(PROOF_VERSION + 1))
+        );
+    }
+    #[test]
+    fn test_serialization_round_trip() {
+        let (proof, verifier_key, preprocessed_table) = setup_proof();
+
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), PROOF_SIZE);
+
+        let decoded = MultiSetEqualityProof::from_bytes(&bytes).unwrap();
+        let mut verifier_transcript = Transcript::new(b"lookup");
+        let ok = decoded
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_serialization_rejects_bad_lengths() {
+        let (proof, _, _) = setup_proof();
+        let bytes = proof.to_bytes();
+
+        for &len in &[1, 9, 9 + G1_SIZE, PROOF_SIZE - 1] {
+            assert_eq!(
+                MultiSetEqualityProof::from_bytes(&bytes[..len]),
+                Err(SerializationError::UnexpectedEnd)
+            );
+        }
+
+        let mut long_bytes = bytes;
+        long_bytes.push(0);
+        assert_eq!(
+            MultiSetEqualityProof::from_bytes(&long_bytes),
+            Err(SerializationError::TrailingBytes)
+        );
+    }
+    #[test]
+    fn test_serialization_domain_limit() {
+        let (proof, _, _) = setup_proof();
+        let mut bytes = proof.to_bytes();
+
+        // A proof claiming an absurd domain size is rejected before its elements are decoded
+        bytes[1..9].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            MultiSetEqualityProof::from_bytes(&bytes),
+            Err(SerializationError::DomainTooLarge {
+                n: u64::MAX,
+                max: DEFAULT_MAX_DOMAIN_SIZE
+            })
+        );
+
+        // The limit can be lowered below the size of a valid proof
+        let bytes = proof.to_bytes();
+        assert_eq!(
+            MultiSetEqualityProof::from_bytes_with_limit(&bytes, proof.n - 1),
+            Err(SerializationError::DomainTooLarge {
+                n: proof.n as u64,
+                max: proof.n - 1
+            })
+        );
+        assert_eq!(
+            MultiSetEqualityProof::from_bytes_with_limit(&bytes, proof.n),
+            Ok(proof)
+        );
+    }
+    #[test]
+    fn test_serialization_corrupted_commitment() {
+        let (proof, verifier_key, preprocessed_table) = setup_proof();
+
+        // Flip a byte of the commitment to f, which follows the version, n and the two opening proofs
+        let mut bytes = proof.to_bytes();
+        bytes[1 + 8 + 2 * G1_SIZE] ^= 1;
+
+        // The corrupted bytes either do not decode to a point, or decode to a proof which is rejected
+        match MultiSetEqualityProof::from_bytes(&bytes) {
+            Err(err) => assert_eq!(err, SerializationError::InvalidData),
+            Ok(corrupted) => {
+                assert_ne!(corrupted, proof);
+                let mut verifier_transcript = Transcript::new(b"lookup");
+                let ok = corrupted
+                    .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+                    .is_ok();
+                assert!(!ok);
+            }
+        }
+    }
+    // Forwards every operation to a transcript, replacing one label with another
+    struct RelabellingTranscript {
+        transcript: Transcript,
+        from: &'static [u8],
+        to: &'static [u8],
+    }
+
+    impl RelabellingTranscript {
+        fn relabel(&self, label: &'static [u8]) -> &'static [u8] {
+            if label == self.from {
+                self.to
+            } else {
+                label
+            }
+        }
+    }
+
+    impl TranscriptProtocol for RelabellingTranscript {
+        fn append_commitment(&mut self, label: &'static [u8], comm: &Commitment<Bls12_381>) {
+            let label = self.relabel(label);
+            self.transcript.append_commitment(label, comm)
+        }
+
+        fn append_scalar(&mut self, label: &'static [u8], s: &Fr) {
+            let label = self.relabel(label);
+            self.transcript.append_scalar(label, s)
+        }
+
+        fn challenge_scalar(&mut self, label: &'static [u8]) -> Fr {
+            let label = self.relabel(label);
+            self.transcript.challenge_scalar(label)
+        }
+    }
+
+    #[test]
+    fn test_mismatched_label() {
+        let (proof, verifier_key, preprocessed_table) = setup_proof();
+
+        // Relabelling a label to itself leaves the transcript unchanged
+        let mut verifier_transcript = RelabellingTranscript {
+            transcript: Transcript::new(b"lookup"),
+            from: labels::BETA,
+            to: labels::BETA,
+        };
+        let ok = proof
+            .verify(&verifier_key, &preprocessed_table, &mut verifier_transcript)
+            .is_ok();
+        assert!(ok);
+
+        // The verifier derives a different challenge if it uses a different label to the prover
+        let mut verifier_transcript = RelabellingTranscript {
+            transcript: Transcript::new(b"lookup"),
+            from: labels::BETA,
+            to: b"beta_mismatch",
+        };
+        assert_eq!(
+            proof.verify(&verifier_key, &preprocessed_table, &mut verifier_transcript),
+            Err(VerificationError::InvalidOpening)
+        );
+    }
+    #[test]
+    fn test_challenges() {
+        let (proof, verifier_key, preprocessed_table) = setup_proof();
+
+        // Derive alpha in the same way as the prover
+        let mut transcript = Transcript::new(b"lookup");
+        transcript.append_scalar(labels::DOMAIN_SIZE, &Fr::from(proof.n as u64));
+        let alpha = transcript.challenge_nonzero_scalar(labels::ALPHA);
+
+        let challenges = proof.challenges(&mut Transcript::new(b"lookup")).unwrap();
+        assert_eq!(challenges.coefficients, [Fr::one(), alpha, alpha * alpha]);
+
+        // The challenges are the same each time they are derived
+        assert_eq!(
+            proof.challenges(&mut Transcript::new(b"lookup")),
+            Ok(challenges.clone())
+        );
+        assert_ne!(
+            proof.challenges(&mut Transcript::new(b"other_lookup")),
+            Ok(challenges.clone())
+        );
+
+        // The options change the challenges in the same way as they change the prover's transcript
+        let with_options = |strategy, sort_order, columns| {
+            proof
+                .challenges_with_options(
+                    strategy,
+                    sort_order,
+                    columns,
+                    &mut Transcript::new(b"lookup"),
+                )
+                .unwrap()
+        };
+        let default = with_options(AggregationStrategy::Powers, SortOrder::Canonical, 3);
+        assert_eq!(default, challenges);
+        let table_position = with_options(AggregationStrategy::Powers, SortOrder::TablePosition, 3);
+        assert_eq!(table_position.coefficients, challenges.coefficients);
+        assert_ne!(table_position.beta, challenges.beta);
+        let four_columns = with_options(AggregationStrategy::Powers, SortOrder::Canonical, 4);
+        assert_eq!(four_columns.coefficients.len(), 4);
+
+        let ok = proof
+            .verify(
+                &verifier_key,
+                &preprocessed_table,
+                &mut Transcript::new(b"lookup"),
+            )
+            .is_ok();
+        assert!(ok);
+    }
+    #[test]
+    fn test_challenges_invalid_domain_size() {
+        let (mut proof, _, _) = setup_proof();
+
+        // A proof read from bytes may claim a domain size which is not a power of two
+        proof.n = 3;
+        assert_eq!(
+            proof.challenges(&mut Transcript::new(b"lookup")),
+            Err(VerificationError::InvalidDomainSize(3))
+        );
+    }
+}
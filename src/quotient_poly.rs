@@ -1,4 +1,5 @@
 use algebra::bls12_381::Fr;
+use algebra_core::fields::{Field, PrimeField};
 use ff_fft::{DensePolynomial as Polynomial, EvaluationDomain};
 use num_traits::identities::{One, Zero};
 // The quotient polynomial will encode the four checks for the multiset equality argument
@@ -32,6 +33,107 @@ pub fn compute(
     beta: Fr,
     gamma: Fr,
 ) -> (Polynomial<Fr>, Polynomial<Fr>) {
+    let sum = compute_numerator(
+        domain, z_poly, f_poly, t_poly, h_1_poly, h_2_poly, beta, gamma,
+    );
+
+    sum.divide_by_vanishing_poly(*domain).unwrap()
+}
+
+/// Returns the default coset shift, which is the multiplicative generator of the field
+pub fn default_coset_shift() -> Fr {
+    Fr::multiplicative_generator()
+}
+
+/// Returns true if the coset `coset_shift * H_4n` of the 4n domain is disjoint from the domain H,
+/// which is when `coset_shift` is not zero and `coset_shift^n` is not a 4th root of unity
+pub fn is_valid_coset_shift(domain: &EvaluationDomain<Fr>, coset_shift: Fr) -> bool {
+    let domain_4n: EvaluationDomain<Fr> = EvaluationDomain::new(4 * domain.size()).unwrap();
+    !coset_shift.is_zero()
+        && domain_4n.elements().take(4).all(|root| {
+            !domain
+                .evaluate_vanishing_polynomial(coset_shift * &root)
+                .is_zero()
+        })
+}
+
+/// Computes the quotient polynomial by dividing on the coset `coset_shift * H_4n` of the 4n domain
+/// The vanishing polynomial is zero on the domain H, and H is a subgroup of the 4n domain,
+/// so the division is done on a coset which is disjoint from H, rather than on the 4n domain itself
+/// Panics if the coset is not disjoint from H, see `is_valid_coset_shift`
+pub fn compute_on_coset(
+    domain: &EvaluationDomain<Fr>,
+    coset_shift: Fr,
+    z_poly: &Polynomial<Fr>,
+    f_poly: &Polynomial<Fr>,
+    t_poly: &Polynomial<Fr>,
+    h_1_poly: &Polynomial<Fr>,
+    h_2_poly: &Polynomial<Fr>,
+    beta: Fr,
+    gamma: Fr,
+) -> Polynomial<Fr> {
+    let sum = compute_numerator(
+        domain, z_poly, f_poly, t_poly, h_1_poly, h_2_poly, beta, gamma,
+    );
+
+    let domain_4n: EvaluationDomain<Fr> = EvaluationDomain::new(4 * domain.size()).unwrap();
+
+    // For an element x = k * w^i of the coset, x^n = k^n * w^{i*n}, where w^n is a 4th root of unity
+    // So the vanishing polynomial only takes four distinct values on the coset
+    let vanishing_inverses: Vec<Fr> = domain_4n
+        .elements()
+        .take(4)
+        .map(|root| {
+            domain
+                .evaluate_vanishing_polynomial(coset_shift * &root)
+                .inverse()
+                .expect("the coset must be disjoint from the domain")
+        })
+        .collect();
+
+    let mut quotient_evals = coset_fft(&domain_4n, coset_shift, &sum);
+    for (i, eval) in quotient_evals.iter_mut().enumerate() {
+        *eval *= &vanishing_inverses[i % 4];
+    }
+
+    coset_ifft(&domain_4n, coset_shift, &quotient_evals)
+}
+
+// Evaluates a polynomial over the coset `coset_shift * domain`
+fn coset_fft(domain: &EvaluationDomain<Fr>, coset_shift: Fr, poly: &Polynomial<Fr>) -> Vec<Fr> {
+    let mut coeffs = poly.coeffs.clone();
+    distribute_powers(&mut coeffs, coset_shift);
+    domain.fft(&coeffs)
+}
+
+// Interpolates the polynomial whose evaluations over the coset `coset_shift * domain` are `evals`
+fn coset_ifft(domain: &EvaluationDomain<Fr>, coset_shift: Fr, evals: &[Fr]) -> Polynomial<Fr> {
+    let mut coeffs = domain.ifft(evals);
+    distribute_powers(&mut coeffs, coset_shift.inverse().unwrap());
+    Polynomial::from_coefficients_vec(coeffs)
+}
+
+// Multiplies the i'th coefficient by g^i
+fn distribute_powers(coeffs: &mut [Fr], g: Fr) {
+    let mut power = Fr::one();
+    for coeff in coeffs.iter_mut() {
+        *coeff *= &power;
+        power *= &g;
+    }
+}
+
+// Computes the sum of the point, interval and term checks, which is divisible by the vanishing polynomial
+// for an honest prover
+fn compute_numerator(
+    domain: &EvaluationDomain<Fr>,
+    z_poly: &Polynomial<Fr>,
+    f_poly: &Polynomial<Fr>,
+    t_poly: &Polynomial<Fr>,
+    h_1_poly: &Polynomial<Fr>,
+    h_2_poly: &Polynomial<Fr>,
+    beta: Fr,
+    gamma: Fr,
+) -> Polynomial<Fr> {
     // 1. Compute Point check polynomial
     let point_check = compute_point_checks(z_poly, domain);
     //2. Compute interval check polynomial
@@ -40,10 +142,8 @@ pub fn compute(
     let term_check = compute_term_check(
         domain, z_poly, f_poly, t_poly, h_1_poly, h_2_poly, beta, gamma,
     );
-    // Compute quotient polynomial
-    let sum = &(&interval_check + &point_check) + &term_check;
 
-    sum.divide_by_vanishing_poly(*domain).unwrap()
+    &(&interval_check + &point_check) + &term_check
 }
 
 fn compute_point_checks(z_poly: &Polynomial<Fr>, domain: &EvaluationDomain<Fr>) -> Polynomial<Fr> {
@@ -305,9 +405,71 @@ mod test {
         let z_evaluations = compute_accumulator_values(&f, &t, &h_1, &h_2, beta, gamma);
         let z_poly = Polynomial::from_coefficients_vec(domain.ifft(&z_evaluations));
 
-        let (_, remainder) = compute(
+        let (quotient, remainder) = compute(
             &domain, &z_poly, &f_poly, &t_poly, &h_1_poly, &h_2_poly, beta, gamma,
         );
         assert!(remainder.is_zero());
+
+        // Dividing on a coset gives the same quotient as long division
+        let coset_quotient = compute_on_coset(
+            &domain,
+            default_coset_shift(),
+            &z_poly,
+            &f_poly,
+            &t_poly,
+            &h_1_poly,
+            &h_2_poly,
+            beta,
+            gamma,
+        );
+        assert_eq!(coset_quotient, quotient);
+
+        // Dividing on the 4n domain itself would divide by zero, as the domain is a subgroup of it
+        let domain_4n: EvaluationDomain<Fr> = EvaluationDomain::new(4 * domain.size()).unwrap();
+        let zero_count = domain_4n
+            .elements()
+            .filter(|x| domain.evaluate_vanishing_polynomial(*x).is_zero())
+            .count();
+        assert_eq!(zero_count, domain.size());
+    }
+    #[test]
+    fn test_is_valid_coset_shift() {
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(8).unwrap();
+        let domain_4n: EvaluationDomain<Fr> = EvaluationDomain::new(32).unwrap();
+
+        assert!(is_valid_coset_shift(&domain, default_coset_shift()));
+        assert!(is_valid_coset_shift(&domain, Fr::from(5u8)));
+
+        // Every element of the 4n domain leaves the coset equal to the 4n domain, which contains H
+        assert!(!is_valid_coset_shift(&domain, Fr::one()));
+        assert!(!is_valid_coset_shift(&domain, domain_4n.group_gen));
+        assert!(!is_valid_coset_shift(&domain, Fr::zero()));
+    }
+    #[test]
+    #[should_panic(expected = "the coset must be disjoint from the domain")]
+    fn test_quotient_poly_coset_intersects_domain() {
+        let f = MultiSet::from_u64s(&[2, 3, 4]);
+        let t = MultiSet::from_u64s(&[2, 3, 4, 5]);
+
+        let domain: EvaluationDomain<Fr> = EvaluationDomain::new(f.len()).unwrap();
+        let beta = Fr::from(10u8);
+        let gamma = Fr::from(11u8);
+
+        let (h_1, h_2) = compute_h1_h2(&f, &t);
+        let z_evaluations = compute_accumulator_values(&f, &t, &h_1, &h_2, beta, gamma);
+        let z_poly = Polynomial::from_coefficients_vec(domain.ifft(&z_evaluations));
+
+        // A shift of one does not move the 4n domain, which contains the domain
+        compute_on_coset(
+            &domain,
+            Fr::one(),
+            &z_poly,
+            &f.to_polynomial(&domain),
+            &t.to_polynomial(&domain),
+            &h_1.to_polynomial(&domain),
+            &h_2.to_polynomial(&domain),
+            beta,
+            gamma,
+        );
     }
 }
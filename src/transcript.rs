@@ -2,8 +2,49 @@ use algebra::bls12_381::Fr;
 use algebra::Bls12_381;
 use algebra::{to_bytes, ToBytes};
 use merlin::Transcript;
+use num_traits::identities::Zero;
 use poly_commit::kzg10::Commitment;
 
+/// Labels used by the prover and the verifier when adding messages to the transcript
+/// and deriving challenges from it. Both sides must use the same labels, in the same order
+pub mod labels {
+    /// Size of the domain
+    pub const DOMAIN_SIZE: &[u8] = b"domain_size";
+    /// Challenge used to aggregate the table columns
+    pub const ALPHA: &[u8] = b"alpha";
+    /// Second challenge used by `AggregationStrategy::IndependentChallenges`
+    pub const ALPHA_2: &[u8] = b"alpha_2";
+    /// Third challenge used by `AggregationStrategy::IndependentChallenges`, for tables with four columns
+    pub const ALPHA_3: &[u8] = b"alpha_3";
+    /// Order used to sort the witness by the table, when it is not `SortOrder::Canonical`
+    pub const SORT_ORDER: &[u8] = b"sort_order";
+
+    pub const H_1_POLY: &[u8] = b"h_1_poly";
+    pub const H_2_POLY: &[u8] = b"h_2_poly";
+    pub const BETA: &[u8] = b"beta";
+    pub const GAMMA: &[u8] = b"gamma";
+    pub const ACCUMULATOR_POLY: &[u8] = b"accumulator_poly";
+    pub const QUOTIENT_POLY: &[u8] = b"quotient_poly";
+    pub const EVALUATION_CHALLENGE: &[u8] = b"evaluation_challenge";
+
+    pub const F_EVAL: &[u8] = b"f_eval";
+    pub const T_EVAL: &[u8] = b"t_eval";
+    pub const H_1_EVAL: &[u8] = b"h_1_eval";
+    pub const H_2_EVAL: &[u8] = b"h_2_eval";
+    pub const Z_EVAL: &[u8] = b"z_eval";
+    pub const Q_EVAL: &[u8] = b"q_eval";
+    pub const T_OMEGA_EVAL: &[u8] = b"t_omega_eval";
+    pub const H_1_OMEGA_EVAL: &[u8] = b"h_1_omega_eval";
+    pub const H_2_OMEGA_EVAL: &[u8] = b"h_2_omega_eval";
+    pub const Z_OMEGA_EVAL: &[u8] = b"z_omega_eval";
+
+    /// Challenge used to aggregate the opening witnesses
+    pub const WITNESS_AGGREGATION: &[u8] = b"witness_aggregation";
+
+    /// Commitment to a multiset which is proven to be equal to a public multiset
+    pub const PUBLIC_POLY: &[u8] = b"public_poly";
+}
+
 pub trait TranscriptProtocol {
     /// Append a `commitment` with the given `label`.
     fn append_commitment(&mut self, label: &'static [u8], comm: &Commitment<Bls12_381>);
@@ -13,6 +54,18 @@ pub trait TranscriptProtocol {
 
     /// Compute a `label`ed challenge variable.
     fn challenge_scalar(&mut self, label: &'static [u8]) -> Fr;
+
+    /// Compute a `label`ed challenge variable, which is guaranteed to be non-zero.
+    /// A zero challenge occurs with probability 1/|Fr|, in which case it is re-derived
+    /// from the updated transcript. Prover and verifier re-derive in the same way.
+    fn challenge_nonzero_scalar(&mut self, label: &'static [u8]) -> Fr {
+        loop {
+            let challenge = self.challenge_scalar(label);
+            if !challenge.is_zero() {
+                return challenge;
+            }
+        }
+    }
 }
 
 impl TranscriptProtocol for Transcript {
@@ -36,3 +89,67 @@ impl TranscriptProtocol for Transcript {
         Fr::rand(&mut rng)
     }
 }
+
+/// A single operation performed on a transcript
+/// Only the label and the number of bytes absorbed are recorded, not the values themselves
+#[cfg(feature = "record")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscriptOp {
+    AppendCommitment { label: &'static [u8], len: usize },
+    AppendScalar { label: &'static [u8], len: usize },
+    Challenge { label: &'static [u8] },
+}
+
+#[cfg(feature = "record")]
+impl TranscriptOp {
+    /// Returns the label of the operation
+    pub fn label(&self) -> &'static [u8] {
+        match self {
+            TranscriptOp::AppendCommitment { label, .. } => *label,
+            TranscriptOp::AppendScalar { label, .. } => *label,
+            TranscriptOp::Challenge { label } => *label,
+        }
+    }
+}
+
+/// Wraps a transcript and records every operation performed on it
+/// This lets auditors check that every prover message is bound into the Fiat-Shamir challenges
+#[cfg(feature = "record")]
+pub struct RecordingTranscript<'a> {
+    transcript: &'a mut dyn TranscriptProtocol,
+    ops: Vec<TranscriptOp>,
+}
+
+#[cfg(feature = "record")]
+impl<'a> RecordingTranscript<'a> {
+    pub fn new(transcript: &'a mut dyn TranscriptProtocol) -> RecordingTranscript<'a> {
+        RecordingTranscript {
+            transcript,
+            ops: Vec::new(),
+        }
+    }
+    /// Returns the recorded operations, in the order that they were performed
+    pub fn into_ops(self) -> Vec<TranscriptOp> {
+        self.ops
+    }
+}
+
+#[cfg(feature = "record")]
+impl<'a> TranscriptProtocol for RecordingTranscript<'a> {
+    fn append_commitment(&mut self, label: &'static [u8], comm: &Commitment<Bls12_381>) {
+        let len = to_bytes![comm].unwrap().len();
+        self.ops.push(TranscriptOp::AppendCommitment { label, len });
+        self.transcript.append_commitment(label, comm)
+    }
+
+    fn append_scalar(&mut self, label: &'static [u8], s: &Fr) {
+        let len = to_bytes![s].unwrap().len();
+        self.ops.push(TranscriptOp::AppendScalar { label, len });
+        self.transcript.append_scalar(label, s)
+    }
+
+    fn challenge_scalar(&mut self, label: &'static [u8]) -> Fr {
+        self.ops.push(TranscriptOp::Challenge { label });
+        self.transcript.challenge_scalar(label)
+    }
+}